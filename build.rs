@@ -0,0 +1,11 @@
+fn main() -> std::io::Result<()> {
+    println!("cargo::rerun-if-changed=build.rs");
+
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo::rerun-if-changed=proto/embedder.proto");
+        tonic_build::compile_protos("proto/embedder.proto")?;
+    }
+
+    Ok(())
+}
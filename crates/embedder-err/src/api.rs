@@ -2,6 +2,8 @@
 
 use thiserror::Error;
 
+#[cfg(feature = "grpc")]
+use embedder_external::tonic;
 use embedder_external::{
     axum::{self, http::StatusCode, response::IntoResponse, Json},
     clap, serde_json,
@@ -65,6 +67,10 @@ pub enum EmbedderAPIError {
     #[error("{0}")]
     SerdeJsonError(#[from] serde_json::Error),
 
+    #[cfg(feature = "grpc")]
+    #[error("{0}")]
+    GrpcError(#[from] tonic::transport::Error),
+
     #[error("User terminated.")]
     UserTerminated,
 
@@ -78,12 +84,32 @@ pub enum EmbedderAPIError {
 
     #[error("{0} is not yet implemented.")]
     NotImplemented(String),
+
+    #[error("Model '{0}' is not available.")]
+    UnknownModel(String),
+
+    #[error("Invalid combination of options: {0}")]
+    InvalidOptions(String),
+
+    #[error("Malformed request: {0}")]
+    BadRequest(String),
 }
 
 impl EmbedderAPIError {
     /// Get the status code for the error.
     pub fn status_code(&self) -> StatusCode {
-        StatusCode::INTERNAL_SERVER_ERROR
+        match self {
+            Self::CannotEmbedInput(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::UnknownModel(_) => StatusCode::NOT_FOUND,
+            Self::InvalidOptions(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::EmbedderError(
+                EmbedderError::UnsupportedDimensions { .. }
+                | EmbedderError::UnsupportedPooling { .. }
+                | EmbedderError::ImageDecodeError { .. },
+            ) => StatusCode::UNPROCESSABLE_ENTITY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
     }
 
     /// Get the variant name of the error.
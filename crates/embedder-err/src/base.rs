@@ -13,14 +13,39 @@ pub enum EmbedderError {
         path: Box<std::path::Path>,
         error: std::io::Error,
     },
+    #[error("Model file {path} failed the integrity check: {error}")]
+    ModelIntegrityError {
+        path: Box<std::path::Path>,
+        error: String,
+    },
+    #[error("Invalid entry for '{name}' in the models manifest: {error}")]
+    ModelManifestError { name: String, error: String },
     #[error("No input provided.")]
     EmptyInputError,
     #[error("Failed to generate embeddings: {0}")]
     FastEmbedError(#[from] fastembed::Error),
     #[error("Failed to load '{name}': {error}")]
-    ModelLoadError { name: &'static str, error: String },
+    ModelLoadError { name: String, error: String },
+    #[error("Failed to read the ONNX model: {0}")]
+    OnnxModelError(String),
     #[error("Filed to transform the output as desired: {0}")]
     OutputTransformError(String),
     #[error("Failed to parse environment variable '{key}': {error}")]
     EnvVarError { key: String, error: String },
+    #[error("Failed to tokenize the input: {0}")]
+    TokenizerError(String),
+    #[error("'{name}' cannot produce embeddings of {dimensions} dimensions; supported dimensions are {supported:?}.")]
+    UnsupportedDimensions {
+        name: String,
+        dimensions: usize,
+        supported: Vec<usize>,
+    },
+    #[error("'{name}' does not support '{pooling}' pooling; supported pooling methods are {supported:?}.")]
+    UnsupportedPooling {
+        name: String,
+        pooling: &'static str,
+        supported: Vec<&'static str>,
+    },
+    #[error("Failed to decode image #{index}: {error}")]
+    ImageDecodeError { index: usize, error: String },
 }
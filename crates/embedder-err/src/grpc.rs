@@ -0,0 +1,22 @@
+//! gRPC specific error conversions.
+
+use embedder_external::{axum::http::StatusCode, tonic};
+
+use crate::EmbedderAPIError;
+
+impl From<EmbedderAPIError> for tonic::Status {
+    /// Map the error to a gRPC status, using the same status codes as the HTTP API.
+    fn from(error: EmbedderAPIError) -> Self {
+        let code = match error.status_code() {
+            StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => {
+                tonic::Code::InvalidArgument
+            }
+            StatusCode::NOT_FOUND => tonic::Code::NotFound,
+            StatusCode::NOT_IMPLEMENTED => tonic::Code::Unimplemented,
+            StatusCode::SERVICE_UNAVAILABLE => tonic::Code::Unavailable,
+            _ => tonic::Code::Internal,
+        };
+
+        tonic::Status::new(code, error.to_string())
+    }
+}
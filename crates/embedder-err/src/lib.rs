@@ -8,3 +8,6 @@ pub use base::*;
 mod api;
 #[cfg(feature = "api")]
 pub use api::*;
+
+#[cfg(feature = "grpc")]
+mod grpc;
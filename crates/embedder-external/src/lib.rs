@@ -10,8 +10,18 @@ pub use axum;
 #[cfg(feature = "clap")]
 pub use clap;
 
+#[cfg(feature = "tonic")]
+pub use tonic;
+
 pub use fastembed;
+pub use half;
+pub use image;
+pub use memmap2;
 pub use ndarray;
+pub use ort;
 pub use serde;
 pub use serde_json;
+pub use sha2;
+pub use tokenizers;
+pub use toml;
 pub use utoipa;
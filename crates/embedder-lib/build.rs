@@ -1,7 +1,33 @@
-use std::{env, fs, io, path};
+use std::{collections::HashMap, env, fs, io, path};
 
 use unicode_ident;
 
+// Shared with `embedder_lib::integrity`.
+#[path = "src/checksums.rs"]
+mod checksums;
+use checksums::{fingerprint, parse_manifest, sha256_hex, MANIFEST_FILE};
+
+// Shared with `embedder_lib::transform::models::manifest`.
+#[path = "src/manifest_keys.rs"]
+mod manifest_keys;
+use manifest_keys::{parse_pooling, parse_quantization, parse_token_pooling};
+
+/// Read the manifest of the model folder into a map of file names to their hex digests.
+fn read_manifest(model_dir: &path::Path) -> io::Result<HashMap<String, String>> {
+    let manifest_path = model_dir.join(MANIFEST_FILE);
+    let contents = fs::read_to_string(&manifest_path).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!(
+                "Could not read {manifest_path:?}: {err}. Run `sha256sum * > {MANIFEST_FILE}` \
+                within the model folder once the model is verified.",
+            ),
+        )
+    })?;
+
+    Ok(parse_manifest(&contents))
+}
+
 /// Build module name
 fn build_module_name(name: &str) -> Option<String> {
     name.chars()
@@ -34,31 +60,467 @@ fn build_module_name(name: &str) -> Option<String> {
         .0
 }
 
-/// Add a model to the binaries library
-fn add_model(model_path: &str, name: &str, file_names: [&str; 5]) -> io::Result<String> {
-    let model_dir = path::Path::new(model_path).join(name);
-    println!("cargo::rerun-if-changed={path}", path = model_dir.display());
+/// The files of the text and reranking models, with the names of their constants.
+const TEXT_MODEL_FILES: [(&str, &str); 5] = [
+    (manifest_keys::TEXT_MODEL_FILES[0], "MODEL_FILE"),
+    (manifest_keys::TEXT_MODEL_FILES[1], "TOKENIZER_FILE"),
+    (manifest_keys::TEXT_MODEL_FILES[2], "CONFIG_FILE"),
+    (
+        manifest_keys::TEXT_MODEL_FILES[3],
+        "SPECIAL_TOKENS_MAP_FILE",
+    ),
+    (manifest_keys::TEXT_MODEL_FILES[4], "TOKENIZER_CONFIG_FILE"),
+];
 
-    let module_codes = Result::<Vec<String>, io::Error>::from_iter(
-        file_names
-            .iter()
-            .zip([
-                "MODEL_FILE",
-                "TOKENIZER_FILE",
-                "CONFIG_FILE",
-                "SPECIAL_TOKENS_MAP_FILE",
-                "TOKENIZER_CONFIG_FILE",
-            ])
-            .map(|(file_name, var_name)| {
-                let file_path = model_dir.join(file_name);
+/// The files of the image models, which have a preprocessor config instead of a tokenizer.
+const IMAGE_MODEL_FILES: [(&str, &str); 2] = [
+    ("model.onnx", "MODEL_FILE"),
+    ("preprocessor_config.json", "PREPROCESSOR_CONFIG_FILE"),
+];
+
+/// The manifest of the embedded models, next to `Cargo.toml`.
+const MODELS_MANIFEST: &str = "models.toml";
+
+/// Build the name of the enum variant of a model, e.g. `BAAIBgeSmallEnV1_5`.
+fn build_variant_name(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .fold(String::new(), |mut acc, word| {
+            if acc.ends_with(|c: char| c.is_ascii_digit())
+                && word.starts_with(|c: char| c.is_ascii_digit())
+            {
+                // Keep the numbers apart, e.g. `v1.5`
+                acc.push('_');
+            }
+            let mut chars = word.chars();
+            acc.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+            acc.extend(chars);
+            acc
+        })
+}
+
+/// An error in the entry of the model in the manifest.
+fn invalid_entry(name: &str, message: impl std::fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid entry for '{name}' in {MODELS_MANIFEST}: {message}"),
+    )
+}
+
+/// A model declared in the manifest.
+struct ModelEntry {
+    name: String,
+    kind: String,
+    feature: Option<String>,
+    module: String,
+    variant: String,
+    binaries: String,
+    table: toml::Table,
+}
+
+impl ModelEntry {
+    /// Parse the `[[model]]` entry of the manifest.
+    fn new(table: toml::Table) -> io::Result<Self> {
+        let name = match table.get("name") {
+            Some(toml::Value::String(name)) => name.clone(),
+            _ => return Err(invalid_entry("model", "each model needs a `name` string.")),
+        };
+        let entry = Self {
+            kind: "dense".to_owned(),
+            feature: None,
+            module: String::new(),
+            variant: build_variant_name(&name),
+            binaries: build_module_name(&name)
+                .ok_or_else(|| invalid_entry(&name, "not a UAX#31 compatible identifier."))?,
+            name,
+            table,
+        };
+
+        let module = match entry.get_str("module")? {
+            Some(module) => module.to_owned(),
+            None => entry
+                .name
+                .rsplit('/')
+                .next()
+                .and_then(build_module_name)
+                .ok_or_else(|| invalid_entry(&entry.name, "set a `module` for the model."))?,
+        };
+
+        Ok(Self {
+            kind: entry.get_str("kind")?.unwrap_or("dense").to_owned(),
+            feature: entry.get_str("feature")?.map(str::to_owned),
+            variant: entry
+                .get_str("variant")?
+                .map_or_else(|| entry.variant.clone(), str::to_owned),
+            module,
+            ..entry
+        })
+    }
+
+    /// The string value of the key, if present.
+    fn get_str(&self, key: &str) -> io::Result<Option<&str>> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(toml::Value::String(value)) => Ok(Some(value)),
+            Some(_) => Err(invalid_entry(
+                &self.name,
+                format!("`{key}` must be a string."),
+            )),
+        }
+    }
+
+    /// The integer values of the key; a single integer is taken as a list of one.
+    fn get_integers(&self, key: &str) -> io::Result<Vec<i64>> {
+        match self.table.get(key) {
+            None => Ok(Vec::new()),
+            Some(toml::Value::Integer(value)) => Ok(vec![*value]),
+            Some(toml::Value::Array(values)) => values
+                .iter()
+                .map(|value| {
+                    value.as_integer().ok_or_else(|| {
+                        invalid_entry(&self.name, format!("`{key}` must only hold integers."))
+                    })
+                })
+                .collect(),
+            Some(_) => Err(invalid_entry(
+                &self.name,
+                format!("`{key}` must be an integer or a list of integers."),
+            )),
+        }
+    }
+
+    /// Whether the cargo feature of the model is enabled, if it has any.
+    // `Option::is_none_or` requires Rust 1.82, which is newer than some of the toolchains
+    // building this crate.
+    #[allow(clippy::unnecessary_map_or)]
+    fn is_enabled(&self) -> bool {
+        self.feature.as_ref().map_or(true, |feature| {
+            env::var_os(format!(
+                "CARGO_FEATURE_{feature}",
+                feature = feature.to_uppercase().replace('-', "_")
+            ))
+            .is_some()
+        })
+    }
+
+    /// The category of the request enum, as used by `embedded_models!`.
+    fn category(&self) -> &'static str {
+        match self.kind.as_str() {
+            "reranker" => "reranker",
+            "image" => "image",
+            _ => "text",
+        }
+    }
+
+    /// The `(file name, constant name)` pairs of the files of the model.
+    fn files(&self) -> io::Result<Vec<(String, &'static str)>> {
+        let constants = match self.kind.as_str() {
+            "image" => IMAGE_MODEL_FILES.as_slice(),
+            _ => TEXT_MODEL_FILES.as_slice(),
+        };
+
+        match self.table.get("files") {
+            None => Ok(constants
+                .iter()
+                .map(|(file_name, var_name)| (file_name.to_string(), *var_name))
+                .collect()),
+            Some(toml::Value::Array(files)) if files.len() == constants.len() => files
+                .iter()
+                .zip(constants)
+                .map(|(file_name, (_, var_name))| {
+                    file_name
+                        .as_str()
+                        .map(|file_name| (file_name.to_owned(), *var_name))
+                        .ok_or_else(|| invalid_entry(&self.name, "`files` must be strings."))
+                })
+                .collect(),
+            Some(_) => Err(invalid_entry(
+                &self.name,
+                format!(
+                    "`files` must list {count} files for a {kind} model.",
+                    count = constants.len(),
+                    kind = self.kind
+                ),
+            )),
+        }
+    }
+
+    /// Add the files of the model to the binaries library.
+    fn binaries_code(&self, model_path: &str) -> io::Result<String> {
+        let files = self.files()?;
+
+        add_model_files(
+            model_path,
+            &self.name,
+            files
+                .iter()
+                .map(|(file_name, var_name)| (file_name.as_str(), *var_name)),
+        )
+    }
+
+    /// The `Option<&str>` expression of the key.
+    fn optional_str_code(&self, key: &str) -> io::Result<String> {
+        Ok(match self.get_str(key)? {
+            Some(value) => format!("Some({value:?})"),
+            None => "None".to_owned(),
+        })
+    }
+
+    /// Declare the module of the model.
+    fn module_code(&self) -> io::Result<String> {
+        let (name, module, binaries) = (&self.name, &self.module, &self.binaries);
+        let output_key = || {
+            self.get_str("output_key")?
+                .ok_or_else(|| invalid_entry(name, "`output_key` is required."))
+        };
+
+        match self.kind.as_str() {
+            "dense" | "sparse" => {
+                let pooling = match parse_pooling(self.get_str("pooling")?)
+                    .map_err(|message| invalid_entry(name, message))?
+                {
+                    Some(pooling) => format!("Some(fastembed::Pooling::{pooling:?})"),
+                    None => "None".to_owned(),
+                };
+                let token_pooling = match parse_token_pooling(self.get_str("token_pooling")?)
+                    .map_err(|message| invalid_entry(name, message))?
+                {
+                    Some(pooling) => format!("Some(Pooling::{pooling:?})"),
+                    None => "None".to_owned(),
+                };
+                let quantization = parse_quantization(self.get_str("quantization")?)
+                    .map_err(|message| invalid_entry(name, message))?;
+                let dimension = match self.get_integers("dimension")?.as_slice() {
+                    [] => "None".to_owned(),
+                    [dimension] => format!("Some({dimension})"),
+                    _ => return Err(invalid_entry(name, "`dimension` must be a single integer.")),
+                };
 
                 Ok(format!(
-                    r#"    pub const {var_name}: &[u8] = include_bytes!({file_path:?});"#,
-                    var_name = var_name,
-                    file_path = file_path
+                    "create_model!(\n    \
+                        module: {module},\n    \
+                        name: {name:?},\n    \
+                        binaries: {binaries},\n    \
+                        kind: ModelKind::{kind},\n    \
+                        output_key: {output_key:?},\n    \
+                        token_output_key: {token_output_key},\n    \
+                        pooling: {pooling},\n    \
+                        token_pooling: {token_pooling},\n    \
+                        quantization: fastembed::QuantizationMode::{quantization:?},\n    \
+                        prompts: PromptTemplates {{\n        \
+                            query: {query},\n        \
+                            document: {document},\n    \
+                        }},\n    \
+                        dimension: {dimension},\n    \
+                        matryoshka_dimensions: [{matryoshka_dimensions}],\n\
+                    );",
+                    kind = if self.kind == "sparse" {
+                        "Sparse"
+                    } else {
+                        "Dense"
+                    },
+                    output_key = output_key()?,
+                    token_output_key = self.optional_str_code("token_output_key")?,
+                    query = self.optional_str_code("query_prompt")?,
+                    document = self.optional_str_code("document_prompt")?,
+                    matryoshka_dimensions = self
+                        .get_integers("matryoshka_dimensions")?
+                        .iter()
+                        .map(|dimension| dimension.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
                 ))
-            }),
-    )?;
+            }
+            "reranker" => Ok(format!(
+                "create_reranker!(\n    \
+                    module: {module},\n    \
+                    name: {name:?},\n    \
+                    binaries: {binaries},\n\
+                );"
+            )),
+            "image" => Ok(format!(
+                "create_image_model!(\n    \
+                    module: {module},\n    \
+                    name: {name:?},\n    \
+                    binaries: {binaries},\n    \
+                    output_key: {output_key:?},\n    \
+                    text_model: {text_model},\n\
+                );",
+                output_key = output_key()?,
+                text_model = self.optional_str_code("text_model")?,
+            )),
+            kind => Err(invalid_entry(name, format!("unsupported `kind` '{kind}'."))),
+        }
+    }
+}
+
+/// Read the entries of the manifest.
+fn read_models_manifest() -> io::Result<Vec<ModelEntry>> {
+    println!("cargo::rerun-if-changed={MODELS_MANIFEST}");
+    let manifest: toml::Table = fs::read_to_string(MODELS_MANIFEST)?
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    match manifest.get("model") {
+        None => Ok(Vec::new()),
+        Some(toml::Value::Array(models)) => models
+            .iter()
+            .map(|model| match model {
+                toml::Value::Table(table) => ModelEntry::new(table.clone()),
+                _ => Err(invalid_entry(
+                    "model",
+                    "`model` must be an array of tables.",
+                )),
+            })
+            .collect(),
+        Some(_) => Err(invalid_entry(
+            "model",
+            "`model` must be an array of tables.",
+        )),
+    }
+}
+
+/// Declare the `embedded_models!` macro, through which the server declares its request enums.
+fn registry_code(models: &[ModelEntry]) -> String {
+    let rules = ["text", "reranker", "image"].map(|category| {
+        let models = models
+            .iter()
+            .filter(|model| model.category() == category)
+            .map(|model| {
+                format!(
+                    "({variant}, {name:?}, {module})",
+                    variant = model.variant,
+                    name = model.name,
+                    module = model.module
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            "    ({category}, $callback:ident!($($args:tt)*)) => {{\n        \
+                $callback! {{ $($args)* [{models}] }}\n    \
+            }};"
+        )
+    });
+
+    format!(
+        "/// Invoke `$callback! {{ $($args)* [$(($variant, $name, $module)),*] }}` with the `text`,\n\
+        /// `reranker` or `image` models embedded in this build, where `$variant` is the name of\n\
+        /// the variant of the request enum, `$name` the name of the model and `$module` its module\n\
+        /// in `embedder_lib::transform::models`.\n\
+        #[macro_export]\n\
+        macro_rules! embedded_models {{\n\
+            {rules}\n\
+        }}",
+        rules = rules.join("\n")
+    )
+}
+
+/// The path of the file to embed for the model file, compressing it with zstd if the `zstd`
+/// feature is enabled.
+///
+/// The compression level can be set with the `MODEL_ZSTD_LEVEL` environment variable.
+#[cfg(feature = "zstd")]
+fn embedded_file(
+    module_name: &str,
+    file_name: &str,
+    _file_path: &path::Path,
+    contents: &[u8],
+) -> io::Result<path::PathBuf> {
+    println!("cargo::rerun-if-env-changed=MODEL_ZSTD_LEVEL");
+    let level = env::var("MODEL_ZSTD_LEVEL")
+        .ok()
+        .and_then(|level| level.parse().ok())
+        .unwrap_or(9);
+
+    let compressed_path = path::Path::new(&env::var("OUT_DIR").unwrap())
+        .join("models")
+        .join(module_name)
+        .join(format!("{file_name}.zst"));
+    if let Some(parent) = compressed_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&compressed_path, zstd::encode_all(contents, level)?)?;
+
+    Ok(compressed_path)
+}
+
+/// The path of the file to embed for the model file, which is the file itself without the
+/// `zstd` feature.
+#[cfg(not(feature = "zstd"))]
+fn embedded_file(
+    _module_name: &str,
+    _file_name: &str,
+    file_path: &path::Path,
+    _contents: &[u8],
+) -> io::Result<path::PathBuf> {
+    Ok(file_path.to_owned())
+}
+
+/// Add the `(file name, constant name)` pairs of a model to the binaries library
+fn add_model_files<'a>(
+    model_path: &str,
+    name: &str,
+    files: impl Iterator<Item = (&'a str, &'a str)>,
+) -> io::Result<String> {
+    let model_dir = path::Path::new(model_path).join(name);
+    println!("cargo::rerun-if-changed={path}", path = model_dir.display());
+
+    let module_name = build_module_name(name).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Model {name} cannot be converted to a UAX#31 compatible identifier.",
+                name = name
+            ),
+        )
+    })?;
+
+    let manifest = read_manifest(&model_dir)?;
+    let mut digests = Vec::new();
+    let (mut compressed_size, mut decompressed_size) = (0, 0);
+
+    let mut module_codes =
+        Result::<Vec<String>, io::Error>::from_iter(files.map(|(file_name, var_name)| {
+            let file_path = model_dir.join(file_name);
+
+            // Refuse to embed any file that does not match the manifest.
+            let expected = manifest.get(file_name).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{file_path:?} is not listed in the {MANIFEST_FILE} of '{name}'."),
+                )
+            })?;
+            let contents = fs::read(&file_path)?;
+            let actual = sha256_hex(&contents);
+            if actual != *expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{file_path:?} has SHA-256 {actual}, expected {expected}."),
+                ));
+            }
+            digests.push(actual);
+
+            let file_path = embedded_file(&module_name, file_name, &file_path, &contents)?;
+            decompressed_size += contents.len();
+            compressed_size += fs::metadata(&file_path)?.len() as usize;
+
+            Ok(format!(
+                r#"    pub const {var_name}: &[u8] = include_bytes!({file_path:?});"#,
+                var_name = var_name,
+                file_path = file_path
+            ))
+        }))?;
+
+    let fingerprint = fingerprint(digests.iter().map(String::as_str));
+    module_codes.push(format!(
+        r#"    pub const FINGERPRINT: &str = "{fingerprint}";"#
+    ));
+    module_codes.push(format!(
+        "    pub const COMPRESSED_SIZE: usize = {compressed_size};\n    \
+        pub const DECOMPRESSED_SIZE: usize = {decompressed_size};"
+    ));
 
     Ok(format!(
         "/// Binaries for '{name}'.\n\
@@ -67,13 +529,7 @@ fn add_model(model_path: &str, name: &str, file_names: [&str; 5]) -> io::Result<
         }}\
         ",
         name = name,
-        module_name = build_module_name(name).ok_or_else(|| io::Error::new(
-            io::ErrorKind::InvalidInput,
-            format!(
-                "Model {name} cannot be converted to a UAX#31 compatible identifier.",
-                name = name
-            )
-        ))?,
+        module_name = module_name,
         module_codes = module_codes.join("\n\n")
     ))
 }
@@ -89,34 +545,29 @@ fn main() -> io::Result<()> {
 
     dest_path.parent().map(|parent| fs::create_dir_all(parent));
 
-    let models = [
-        #[cfg(feature = "sentence_transformers_all_minilm_l6_v2")]
-        add_model(
-            &model_dir,
-            "sentence-transformers/all-MiniLM-L6-v2",
-            [
-                "model.onnx",
-                "tokenizer.json",
-                "config.json",
-                "special_tokens_map.json",
-                "tokenizer_config.json",
-            ],
-        )?,
-        #[cfg(feature = "sentence_transformers_all_mpnet_base_v2")]
-        add_model(
-            &model_dir,
-            "sentence-transformers/all-mpnet-base-v2",
-            [
-                "model.onnx",
-                "tokenizer.json",
-                "config.json",
-                "special_tokens_map.json",
-                "tokenizer_config.json",
-            ],
-        )?,
-    ];
-
-    fs::write(&dest_path, models.join("\n\n"))?;
+    let models = read_models_manifest()?
+        .into_iter()
+        .filter(ModelEntry::is_enabled)
+        .collect::<Vec<_>>();
+
+    let binaries = models
+        .iter()
+        .map(|model| model.binaries_code(&model_dir))
+        .collect::<io::Result<Vec<_>>>()?;
+    fs::write(&dest_path, binaries.join("\n\n"))?;
+
+    let modules = models
+        .iter()
+        .map(ModelEntry::module_code)
+        .collect::<io::Result<Vec<_>>>()?;
+    fs::write(
+        dest_path.with_file_name("generated.rs"),
+        format!(
+            "{modules}\n\n{registry}\n",
+            modules = modules.join("\n"),
+            registry = registry_code(&models)
+        ),
+    )?;
 
     // Tell Cargo that if the english words changes, to rerun this build script.
     // println!(format!"cargo::rerun-if-changed=data/words/en_common_words.csv");
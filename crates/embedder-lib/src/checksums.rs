@@ -0,0 +1,51 @@
+//! The `SHA256SUMS` manifests and the fingerprints of the model files.
+//!
+//! This is included by both [`crate::integrity`] and `build.rs`, so it may only depend on `std`
+//! and `sha2`.
+
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
+
+/// The name of the manifest within each model folder.
+pub const MANIFEST_FILE: &str = "SHA256SUMS";
+
+/// The lowercase hex SHA-256 digest of the bytes.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Parse a `sha256sum` manifest into a map of file names to their hex digests.
+///
+/// Both the text (`<digest>  <name>`) and binary (`<digest> *<name>`) modes are accepted;
+/// blank lines and `#` comments are ignored.
+pub fn parse_manifest(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (digest, name) = line.split_once(char::is_whitespace)?;
+            let name = name.trim_start();
+            let name = name.strip_prefix('*').unwrap_or(name);
+
+            Some((name.to_owned(), digest.to_ascii_lowercase()))
+        })
+        .collect()
+}
+
+/// The fingerprint of a model, given the hex digests of its files in a fixed order.
+///
+/// This is the SHA-256 of the digests, each followed by a newline.
+pub fn fingerprint<'d>(digests: impl IntoIterator<Item = &'d str>) -> String {
+    sha256_hex(
+        digests
+            .into_iter()
+            .flat_map(|digest| digest.bytes().chain(std::iter::once(b'\n')))
+            .collect::<Vec<u8>>()
+            .as_slice(),
+    )
+}
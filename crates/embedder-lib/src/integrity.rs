@@ -0,0 +1,99 @@
+//! Integrity verification of the model files against their `SHA256SUMS` manifest.
+//!
+//! The manifest is the output of `sha256sum` within the model folder, i.e. one
+//! `<hex digest>  <file name>` line per file. The same check is done by `build.rs` for the
+//! embedded models, which fails the build instead.
+
+use std::path::Path;
+
+use embedder_err::EmbedderError;
+
+// Shared with `build.rs`, which verifies the embedded models with the same functions.
+#[path = "checksums.rs"]
+mod checksums;
+pub use checksums::*;
+
+/// Verify the contents of the files in `dir` against the manifest in the same folder.
+///
+/// `files` are the `(file name, contents)` of each file. Returns the hex digests of the files
+/// in the same order, or [`EmbedderError::ModelIntegrityError`] if the manifest is missing,
+/// does not list one of the files, or lists a different digest.
+pub fn verify_files(dir: &Path, files: &[(&str, &[u8])]) -> Result<Vec<String>, EmbedderError> {
+    let manifest_path = dir.join(MANIFEST_FILE);
+    let manifest = std::fs::read_to_string(&manifest_path)
+        .map(|contents| parse_manifest(&contents))
+        .map_err(|err| EmbedderError::ModelIntegrityError {
+            path: manifest_path.clone().into(),
+            error: err.to_string(),
+        })?;
+
+    files
+        .iter()
+        .map(|(name, contents)| {
+            let path = dir.join(name);
+            let expected =
+                manifest
+                    .get(*name)
+                    .ok_or_else(|| EmbedderError::ModelIntegrityError {
+                        path: path.clone().into(),
+                        error: format!("not listed in {manifest_path:?}"),
+                    })?;
+            let actual = sha256_hex(contents);
+
+            if actual == *expected {
+                Ok(actual)
+            } else {
+                Err(EmbedderError::ModelIntegrityError {
+                    path: path.into(),
+                    error: format!("expected SHA-256 {expected}, found {actual}"),
+                })
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn verify_against_manifest() {
+        let dir = std::env::temp_dir().join(format!("embedder-integrity-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Failed to create the folder.");
+
+        let model = b"not really an onnx model".as_slice();
+        let tokenizer = b"{}".as_slice();
+        std::fs::write(
+            dir.join(MANIFEST_FILE),
+            format!(
+                "{model}  model.onnx\n{tokenizer} *tokenizer.json\n",
+                model = sha256_hex(model),
+                tokenizer = sha256_hex(tokenizer).to_uppercase(),
+            ),
+        )
+        .expect("Failed to write the manifest.");
+
+        let digests = verify_files(
+            &dir,
+            &[("model.onnx", model), ("tokenizer.json", tokenizer)],
+        )
+        .expect("The files should match the manifest.");
+        assert_eq!(digests, vec![sha256_hex(model), sha256_hex(tokenizer)]);
+        assert_eq!(
+            fingerprint(digests.iter().map(String::as_str)).len(),
+            64,
+            "The fingerprint should be a hex SHA-256."
+        );
+
+        assert!(matches!(
+            verify_files(&dir, &[("model.onnx", b"swapped".as_slice())]),
+            Err(EmbedderError::ModelIntegrityError { .. })
+        ));
+        assert!(matches!(
+            verify_files(&dir, &[("config.json", b"{}".as_slice())]),
+            Err(EmbedderError::ModelIntegrityError { .. })
+        ));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
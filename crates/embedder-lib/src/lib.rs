@@ -1,5 +1,9 @@
 pub(crate) mod common;
 
+pub mod integrity;
+
+pub mod quantize;
+
 pub mod transform;
 
 pub use embedder_external::fastembed::Embedding;
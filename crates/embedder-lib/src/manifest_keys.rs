@@ -0,0 +1,72 @@
+//! The values of the keys of the `[[model]]` entries of `models.toml`.
+//!
+//! This is included by both `transform::models::manifest` and `build.rs`, so that the models
+//! loaded at runtime accept exactly the values of the embedded ones; it may only depend on `std`.
+//! The variants are named after the types they stand for, which `build.rs` writes out as code.
+
+/// The default `files` of the text models, in the order of the `files` key.
+pub const TEXT_MODEL_FILES: [&str; 5] = [
+    "model.onnx",
+    "tokenizer.json",
+    "config.json",
+    "special_tokens_map.json",
+    "tokenizer_config.json",
+];
+
+/// The `pooling` key, i.e. `fastembed::Pooling`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModelPooling {
+    Mean,
+    Cls,
+}
+
+/// The `token_pooling` key, i.e. `transform::Pooling`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenPooling {
+    Mean,
+    Cls,
+    Max,
+    LastToken,
+    WeightedMean,
+}
+
+/// The `quantization` key, i.e. `fastembed::QuantizationMode`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quantization {
+    None,
+    Static,
+    Dynamic,
+}
+
+/// Parse the `pooling` key; `none` is the same as leaving it out.
+pub fn parse_pooling(value: Option<&str>) -> Result<Option<ModelPooling>, String> {
+    match value {
+        None | Some("none") => Ok(None),
+        Some("mean") => Ok(Some(ModelPooling::Mean)),
+        Some("cls") => Ok(Some(ModelPooling::Cls)),
+        Some(pooling) => Err(format!("unsupported `pooling` '{pooling}'.")),
+    }
+}
+
+/// Parse the `token_pooling` key; `none` is the same as leaving it out.
+pub fn parse_token_pooling(value: Option<&str>) -> Result<Option<TokenPooling>, String> {
+    match value {
+        None | Some("none") => Ok(None),
+        Some("mean") => Ok(Some(TokenPooling::Mean)),
+        Some("cls") => Ok(Some(TokenPooling::Cls)),
+        Some("max") => Ok(Some(TokenPooling::Max)),
+        Some("last_token") => Ok(Some(TokenPooling::LastToken)),
+        Some("weighted_mean") => Ok(Some(TokenPooling::WeightedMean)),
+        Some(pooling) => Err(format!("unsupported `token_pooling` '{pooling}'.")),
+    }
+}
+
+/// Parse the `quantization` key, [`Quantization::None`] if left out.
+pub fn parse_quantization(value: Option<&str>) -> Result<Quantization, String> {
+    match value {
+        None | Some("none") => Ok(Quantization::None),
+        Some("static") => Ok(Quantization::Static),
+        Some("dynamic") => Ok(Quantization::Dynamic),
+        Some(quantization) => Err(format!("unsupported `quantization` '{quantization}'.")),
+    }
+}
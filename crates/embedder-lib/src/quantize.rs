@@ -0,0 +1,174 @@
+//! Reduced-precision representations of the embeddings.
+//!
+//! These operate on the normalized output of [`CanTransform::embed_to_array`], so that
+//! clients storing embeddings at lower precisions do not need to receive the full
+//! precision floats just to throw them away.
+//!
+//! [`CanTransform::embed_to_array`]: crate::transform::CanTransform::embed_to_array
+
+use embedder_external::{half::f16, ndarray};
+
+/// Convert the embeddings to IEEE 754 half precision floats.
+pub fn to_float16(array: &ndarray::Array2<f32>) -> ndarray::Array2<f16> {
+    array.mapv(f16::from_f32)
+}
+
+/// Embeddings quantized to signed 8-bit integers.
+///
+/// Each row can be dequantized with `scale * (value - zero_point)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Int8Embeddings {
+    pub values: ndarray::Array2<i8>,
+    pub scales: Vec<f32>,
+    pub zero_points: Vec<i32>,
+}
+
+/// Compute the affine quantization parameters mapping `[min, max]` onto `[-128, 127]`.
+fn int8_parameters(min: f32, max: f32) -> (f32, i32) {
+    // Always include zero in the range, so that zero is exactly representable.
+    let (min, max) = (min.min(0.0), max.max(0.0));
+    let scale = if max > min { (max - min) / 255.0 } else { 1.0 };
+    let zero_point = (-128.0 - min / scale).round().clamp(-128.0, 127.0) as i32;
+
+    (scale, zero_point)
+}
+
+/// Check that a calibrated `(min, max)` range can be used by [`to_int8`].
+///
+/// Both bounds must be finite, and `min` must be below `max`; any other range would give a
+/// zero, infinite or NaN scale.
+pub fn check_int8_range(min: f32, max: f32) -> Result<(), String> {
+    if !min.is_finite() || !max.is_finite() {
+        return Err(format!(
+            "`int8_range` must be finite, found [{min}, {max}]."
+        ));
+    }
+    if min >= max {
+        return Err(format!(
+            "`int8_range` must be an increasing [min, max] range, found [{min}, {max}]."
+        ));
+    }
+
+    Ok(())
+}
+
+/// Quantize the embeddings to signed 8-bit integers.
+///
+/// If `range` is given, all the rows share the same calibrated `(min, max)` range, which
+/// keeps the quantized values comparable across requests; values outside of the range are
+/// clipped. Otherwise, each row is quantized using its own range. The range must pass
+/// [`check_int8_range`].
+pub fn to_int8(array: &ndarray::Array2<f32>, range: Option<(f32, f32)>) -> Int8Embeddings {
+    let parameters = array
+        .rows()
+        .into_iter()
+        .map(|row| {
+            let (min, max) = range.unwrap_or_else(|| {
+                row.iter()
+                    .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+                        (min.min(*value), max.max(*value))
+                    })
+            });
+            int8_parameters(min, max)
+        })
+        .collect::<Vec<_>>();
+
+    let mut values = ndarray::Array2::<i8>::zeros(array.raw_dim());
+    values
+        .rows_mut()
+        .into_iter()
+        .zip(array.rows())
+        .zip(parameters.iter())
+        .for_each(|((mut quantized, row), (scale, zero_point))| {
+            quantized.iter_mut().zip(row.iter()).for_each(|(q, value)| {
+                *q = ((value / scale).round() + *zero_point as f32).clamp(-128.0, 127.0) as i8;
+            });
+        });
+
+    let (scales, zero_points) = parameters.into_iter().unzip();
+
+    Int8Embeddings {
+        values,
+        scales,
+        zero_points,
+    }
+}
+
+/// Binarize the embeddings by their signs, packing 8 dimensions into each byte.
+///
+/// Positive values are set to `1`. The bits are packed in big-endian order, consistent with
+/// `numpy.packbits`; the last byte of each row is padded with zeros if the number of
+/// dimensions is not a multiple of 8.
+pub fn to_binary(array: &ndarray::Array2<f32>) -> ndarray::Array2<u8> {
+    let (rows, dimensions) = array.dim();
+    let mut packed = ndarray::Array2::<u8>::zeros((rows, dimensions.div_ceil(8)));
+
+    packed
+        .rows_mut()
+        .into_iter()
+        .zip(array.rows())
+        .for_each(|(mut bytes, row)| {
+            row.iter().enumerate().for_each(|(idx, value)| {
+                if *value > 0.0 {
+                    bytes[idx / 8] |= 0x80 >> (idx % 8);
+                }
+            });
+        });
+
+    packed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> ndarray::Array2<f32> {
+        ndarray::arr2(&[
+            [0.5, -0.25, 0.0, 0.75, -1.0, 0.1, 0.2, -0.3, 0.9],
+            [-0.1, 0.1, -0.2, 0.2, -0.3, 0.3, -0.4, 0.4, -0.5],
+        ])
+    }
+
+    #[test]
+    fn int8_round_trip() {
+        let array = sample();
+
+        for range in [None, Some((-1.0, 1.0))] {
+            let quantized = to_int8(&array, range);
+
+            quantized
+                .values
+                .rows()
+                .into_iter()
+                .zip(array.rows())
+                .zip(quantized.scales.iter().zip(quantized.zero_points.iter()))
+                .for_each(|((values, row), (scale, zero_point))| {
+                    values.iter().zip(row.iter()).for_each(|(value, expected)| {
+                        let actual = scale * (*value as i32 - zero_point) as f32;
+                        assert!(
+                            (actual - expected).abs() <= *scale,
+                            "Expected {expected}, found {actual} with {range:?}."
+                        );
+                    });
+                });
+        }
+    }
+
+    #[test]
+    fn int8_range_validation() {
+        assert!(check_int8_range(-1.0, 1.0).is_ok());
+        assert!(check_int8_range(0.5, 0.5).is_err());
+        assert!(check_int8_range(1.0, -1.0).is_err());
+        assert!(check_int8_range(f32::NAN, 1.0).is_err());
+        assert!(check_int8_range(-1.0, f32::INFINITY).is_err());
+    }
+
+    #[test]
+    fn binary_packing() {
+        let packed = to_binary(&sample());
+
+        assert_eq!(packed.dim(), (2, 2));
+        assert_eq!(packed.row(0).to_vec(), vec![0b1001_0110, 0b1000_0000]);
+        assert_eq!(packed.row(1).to_vec(), vec![0b0101_0101, 0b0000_0000]);
+    }
+}
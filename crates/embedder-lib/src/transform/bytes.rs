@@ -0,0 +1,104 @@
+//! The bytes of the model files, borrowed or memory-mapped wherever possible.
+//!
+//! Copying an ONNX model into the heap before handing it to ONNX Runtime keeps it resident
+//! twice during the load. The embedded files are borrowed from the binary, and the files on disk
+//! are memory-mapped, so that their pages are backed by the file and can be evicted; only the
+//! APIs taking owned bytes, such as those of `fastembed`, still need a copy with
+//! [`ModelBytes::into_vec`].
+
+use std::fs::File;
+use std::ops::Deref;
+use std::path::Path;
+
+use embedder_external::memmap2::Mmap;
+
+/// The bytes of a model file.
+#[derive(Debug)]
+pub enum ModelBytes {
+    /// Embedded in the binary.
+    Static(&'static [u8]),
+    /// Memory-mapped from a file on disk.
+    Mapped(Mmap),
+    /// Owned by the heap, e.g. once decompressed.
+    Owned(Vec<u8>),
+}
+
+impl ModelBytes {
+    /// Memory-map the file at `path`.
+    ///
+    /// The file must not be modified while it is mapped; the model files are only ever replaced
+    /// as a whole, which leaves the existing mapping intact.
+    pub fn map(path: &Path) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+
+        if file.metadata()?.len() == 0 {
+            // Empty files cannot be mapped on all platforms.
+            return Ok(Self::Owned(Vec::new()));
+        }
+
+        // SAFETY: see above; a file truncated underneath the mapping would fault on access.
+        unsafe { Mmap::map(&file) }.map(Self::Mapped)
+    }
+
+    /// Take the bytes into the heap, copying them unless they are already owned.
+    ///
+    /// This is only for the APIs that require a `Vec<u8>`, such as those of [`fastembed`].
+    ///
+    /// [`fastembed`]: embedder_external::fastembed
+    pub fn into_vec(self) -> Vec<u8> {
+        match self {
+            Self::Owned(bytes) => bytes,
+            bytes => bytes.to_vec(),
+        }
+    }
+}
+
+impl Deref for ModelBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Static(bytes) => bytes,
+            Self::Mapped(mmap) => mmap,
+            Self::Owned(bytes) => bytes,
+        }
+    }
+}
+
+impl From<&'static [u8]> for ModelBytes {
+    fn from(bytes: &'static [u8]) -> Self {
+        Self::Static(bytes)
+    }
+}
+
+impl From<Vec<u8>> for ModelBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::Owned(bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn map_file() {
+        let dir = std::env::temp_dir().join(format!("embedder-bytes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Failed to create the folder.");
+
+        let path = dir.join("model.onnx");
+        std::fs::write(&path, b"not really an onnx model").expect("Failed to write the file.");
+        let bytes = ModelBytes::map(&path).expect("Failed to map the file.");
+        assert!(matches!(bytes, ModelBytes::Mapped(_)));
+        assert_eq!(&*bytes, b"not really an onnx model");
+        assert_eq!(bytes.into_vec(), b"not really an onnx model".to_vec());
+
+        let empty = dir.join("empty.json");
+        std::fs::write(&empty, b"").expect("Failed to write the file.");
+        assert!(ModelBytes::map(&empty)
+            .expect("Failed to map the empty file.")
+            .is_empty());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
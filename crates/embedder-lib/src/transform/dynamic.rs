@@ -0,0 +1,383 @@
+//! An object-safe companion of [`CanTransform`].
+//!
+//! [`CanTransform`] has generic methods, so it cannot be used as a trait object. Every
+//! [`CanTransform`] model is also a [`DynEmbedder`], which takes owned [`String`]s instead, so
+//! that models of different types can be held together as `Arc<dyn DynEmbedder>`, e.g. in a
+//! map of models by their names.
+
+use super::models::{EmbeddedSize, HasEmbeddedSize};
+use super::{
+    traits::CanTransform, EmbedOptions, InputType, ModelKind, OutputSelection, Pooling,
+    PromptTemplates, SparseEmbedding, TokenizedDocument,
+};
+use embedder_err::EmbedderError;
+use embedder_external::{fastembed, ndarray};
+
+/// The object-safe methods of [`CanTransform`]; see the documentation of each method there.
+///
+/// This is implemented for all [`CanTransform`] models. Since the models are typically
+/// [`std::sync::Arc`]s themselves, e.g. `Arc<custom::Model>`, a trait object is created with
+/// `Arc::new(model) as Arc<dyn DynEmbedder>`, or borrowed without any allocation, e.g. from the
+/// `&'static Arc<Model>` returned by the `singleton()` of the embedded models.
+pub trait DynEmbedder: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn kind(&self) -> ModelKind;
+
+    fn output_selection(&self) -> Option<OutputSelection>;
+
+    fn pooling(&self) -> Option<fastembed::Pooling>;
+
+    fn token_pooling(&self) -> Option<Pooling>;
+
+    fn supported_pooling(&self) -> Vec<Pooling>;
+
+    fn prompts(&self) -> PromptTemplates<'_>;
+
+    fn dimension(&self) -> Option<usize>;
+
+    fn matryoshka_dimensions(&self) -> Vec<usize>;
+
+    fn fingerprint(&self) -> Option<String>;
+
+    fn embedded_size(&self) -> Option<EmbeddedSize>;
+
+    fn max_input_length(&self) -> Option<usize>;
+
+    fn embed_to_array(
+        &self,
+        texts: Vec<String>,
+        batch_size: Option<usize>,
+    ) -> Result<ndarray::Array2<f32>, EmbedderError>;
+
+    fn embed_with_options(
+        &self,
+        texts: Vec<String>,
+        batch_size: Option<usize>,
+        options: &EmbedOptions,
+    ) -> Result<ndarray::Array2<f32>, EmbedderError>;
+
+    fn embed_to_pooled_array(
+        &self,
+        texts: Vec<String>,
+        batch_size: Option<usize>,
+    ) -> Result<ndarray::Array2<f32>, EmbedderError>;
+
+    fn embed_to_token_arrays(
+        &self,
+        texts: Vec<String>,
+        batch_size: Option<usize>,
+    ) -> Result<Vec<ndarray::Array2<f32>>, EmbedderError>;
+
+    fn embed_to_sparse(
+        &self,
+        texts: Vec<String>,
+        batch_size: Option<usize>,
+    ) -> Result<Vec<SparseEmbedding>, EmbedderError>;
+
+    fn decode_terms(&self, indices: &[u32]) -> Vec<String>;
+
+    fn tokenize(
+        &self,
+        texts: &[String],
+        add_special_tokens: bool,
+    ) -> Result<Vec<TokenizedDocument>, EmbedderError>;
+
+    fn tokenize_with_options(
+        &self,
+        texts: &[String],
+        input_type: Option<InputType>,
+    ) -> Result<Vec<TokenizedDocument>, EmbedderError>;
+
+    fn detokenize(
+        &self,
+        ids: &[Vec<u32>],
+        skip_special_tokens: bool,
+    ) -> Result<Vec<String>, EmbedderError>;
+}
+
+impl<T: CanTransform + Send + Sync> DynEmbedder for T {
+    fn name(&self) -> &str {
+        CanTransform::name(self)
+    }
+
+    fn kind(&self) -> ModelKind {
+        CanTransform::kind(self)
+    }
+
+    fn output_selection(&self) -> Option<OutputSelection> {
+        CanTransform::output_selection(self)
+    }
+
+    fn pooling(&self) -> Option<fastembed::Pooling> {
+        CanTransform::pooling(self)
+    }
+
+    fn token_pooling(&self) -> Option<Pooling> {
+        CanTransform::token_pooling(self)
+    }
+
+    fn supported_pooling(&self) -> Vec<Pooling> {
+        CanTransform::supported_pooling(self)
+    }
+
+    fn prompts(&self) -> PromptTemplates<'_> {
+        CanTransform::prompts(self)
+    }
+
+    fn dimension(&self) -> Option<usize> {
+        CanTransform::dimension(self)
+    }
+
+    fn matryoshka_dimensions(&self) -> Vec<usize> {
+        CanTransform::matryoshka_dimensions(self)
+    }
+
+    fn fingerprint(&self) -> Option<String> {
+        CanTransform::fingerprint(self)
+    }
+
+    fn embedded_size(&self) -> Option<EmbeddedSize> {
+        HasEmbeddedSize::embedded_size(self)
+    }
+
+    fn max_input_length(&self) -> Option<usize> {
+        CanTransform::max_input_length(self)
+    }
+
+    fn embed_to_array(
+        &self,
+        texts: Vec<String>,
+        batch_size: Option<usize>,
+    ) -> Result<ndarray::Array2<f32>, EmbedderError> {
+        CanTransform::embed_to_array(self, texts, batch_size)
+    }
+
+    fn embed_with_options(
+        &self,
+        texts: Vec<String>,
+        batch_size: Option<usize>,
+        options: &EmbedOptions,
+    ) -> Result<ndarray::Array2<f32>, EmbedderError> {
+        CanTransform::embed_with_options(self, texts, batch_size, options)
+    }
+
+    fn embed_to_pooled_array(
+        &self,
+        texts: Vec<String>,
+        batch_size: Option<usize>,
+    ) -> Result<ndarray::Array2<f32>, EmbedderError> {
+        CanTransform::embed_to_pooled_array(self, texts, batch_size)
+    }
+
+    fn embed_to_token_arrays(
+        &self,
+        texts: Vec<String>,
+        batch_size: Option<usize>,
+    ) -> Result<Vec<ndarray::Array2<f32>>, EmbedderError> {
+        CanTransform::embed_to_token_arrays(self, texts, batch_size)
+    }
+
+    fn embed_to_sparse(
+        &self,
+        texts: Vec<String>,
+        batch_size: Option<usize>,
+    ) -> Result<Vec<SparseEmbedding>, EmbedderError> {
+        CanTransform::embed_to_sparse(self, texts, batch_size)
+    }
+
+    fn decode_terms(&self, indices: &[u32]) -> Vec<String> {
+        CanTransform::decode_terms(self, indices)
+    }
+
+    fn tokenize(
+        &self,
+        texts: &[String],
+        add_special_tokens: bool,
+    ) -> Result<Vec<TokenizedDocument>, EmbedderError> {
+        CanTransform::tokenize(self, texts, add_special_tokens)
+    }
+
+    fn tokenize_with_options(
+        &self,
+        texts: &[String],
+        input_type: Option<InputType>,
+    ) -> Result<Vec<TokenizedDocument>, EmbedderError> {
+        CanTransform::tokenize_with_options(self, texts, input_type)
+    }
+
+    fn detokenize(
+        &self,
+        ids: &[Vec<u32>],
+        skip_special_tokens: bool,
+    ) -> Result<Vec<String>, EmbedderError> {
+        CanTransform::detokenize(self, ids, skip_special_tokens)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transform::{models::HasEmbeddedSize, Tokenizer};
+    use embedder_external::tokenizers;
+
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    /// A model without a session, for the methods that do not run it.
+    struct Stub {
+        dimension: usize,
+        token_output_key: Option<&'static str>,
+        tokenizer: Tokenizer,
+    }
+
+    impl Stub {
+        fn new(dimension: usize) -> Self {
+            let model = tokenizers::models::wordlevel::WordLevel::builder()
+                .vocab(HashMap::from([
+                    ("[UNK]".to_owned(), 0),
+                    ("foo".to_owned(), 1),
+                ]))
+                .unk_token("[UNK]".to_owned())
+                .build()
+                .expect("Failed to build the tokenizer.");
+            let mut tokenizer = Tokenizer::new(model.into());
+            tokenizer.with_pre_tokenizer(tokenizers::pre_tokenizers::whitespace::Whitespace);
+
+            Self {
+                dimension,
+                token_output_key: Some("last_hidden_state"),
+                tokenizer,
+            }
+        }
+    }
+
+    impl HasEmbeddedSize for Stub {}
+
+    impl CanTransform for Stub {
+        fn name(&self) -> &str {
+            "stub"
+        }
+
+        fn output_key(&self) -> &str {
+            "last_hidden_state"
+        }
+
+        fn token_output_key(&self) -> Option<&str> {
+            self.token_output_key
+        }
+
+        fn tokenizer(&self) -> &Tokenizer {
+            &self.tokenizer
+        }
+
+        fn pooling(&self) -> Option<fastembed::Pooling> {
+            Some(fastembed::Pooling::Mean)
+        }
+
+        fn dimension(&self) -> Option<usize> {
+            Some(self.dimension)
+        }
+
+        fn transform<'e, 'r, 's, S: AsRef<str> + Send + Sync>(
+            &'e self,
+            _texts: Vec<S>,
+            _batch_size: Option<usize>,
+        ) -> Result<fastembed::EmbeddingOutput<'r, 's>, EmbedderError>
+        where
+            'e: 'r,
+            'e: 's,
+        {
+            Err(EmbedderError::OutputTransformError(
+                "The stub has no session.".to_owned(),
+            ))
+        }
+    }
+
+    #[test]
+    fn stubs_by_name() {
+        let models: HashMap<&str, Arc<dyn DynEmbedder>> = HashMap::from([
+            ("small", Arc::new(Stub::new(8)) as Arc<dyn DynEmbedder>),
+            ("large", Arc::new(Stub::new(32)) as Arc<dyn DynEmbedder>),
+        ]);
+
+        assert_eq!(models["small"].dimension(), Some(8));
+        assert_eq!(models["large"].dimension(), Some(32));
+
+        let model = &models["small"];
+        assert_eq!(model.name(), "stub");
+        assert_eq!(model.kind(), ModelKind::Dense);
+        assert!(!model.supported_pooling().contains(&Pooling::None));
+        assert!(model.supported_pooling().contains(&Pooling::Max));
+        assert_eq!(model.embedded_size(), None);
+        assert_eq!(model.fingerprint(), None);
+
+        let documents = model
+            .tokenize(&["foo bar".to_owned()], false)
+            .expect("Failed to tokenize the documents.");
+        assert_eq!(
+            documents[0]
+                .tokens
+                .iter()
+                .map(|token| token.id)
+                .collect::<Vec<_>>(),
+            [1, 0]
+        );
+
+        assert!(matches!(
+            model.embed_to_array(vec!["foo".to_owned()], None),
+            Err(EmbedderError::OutputTransformError(_))
+        ));
+
+        // The models can also be borrowed as trait objects, e.g. from their singleton.
+        let stub = Stub::new(16);
+        let borrowed: &dyn DynEmbedder = &stub;
+        assert_eq!(borrowed.dimension(), Some(16));
+    }
+
+    #[test]
+    fn token_output_is_opt_in() {
+        let stub = Stub {
+            token_output_key: None,
+            ..Stub::new(8)
+        };
+
+        // The output is pooled by the model, and cannot be pooled any other way.
+        assert!(CanTransform::supported_pooling(&stub).is_empty());
+        assert!(matches!(
+            stub.validate_options(&EmbedOptions {
+                pooling: Some(Pooling::Max),
+                ..Default::default()
+            }),
+            Err(EmbedderError::UnsupportedPooling { .. })
+        ));
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn models_by_name() {
+        use crate::transform::models::mock::{self, MockOptions};
+
+        let mock = |dimension| {
+            mock::Model::with_options(MockOptions {
+                dimension,
+                ..Default::default()
+            })
+            .map(|model| Arc::new(model) as Arc<dyn DynEmbedder>)
+            .expect("Failed to create the mock model.")
+        };
+        let models = HashMap::from([("small", mock(8)), ("large", mock(32))]);
+
+        models.iter().for_each(|(name, model)| {
+            let embeddings = model
+                .embed_to_array(vec!["Hello, world!".to_owned()], None)
+                .expect("Failed to embed the documents.");
+
+            assert_eq!(
+                Some(embeddings.ncols()),
+                model.dimension(),
+                "Mismatch for {name}."
+            );
+        });
+    }
+}
@@ -0,0 +1,28 @@
+//! The kinds of models, and of the embeddings they produce.
+
+/// The kind of embeddings a model produces.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ModelKind {
+    /// One dense vector per document.
+    #[default]
+    Dense,
+    /// One sparse vector over the vocabulary per document.
+    Sparse,
+    /// A relevance score per query and document pair, see
+    /// [`CanRerank`](super::CanRerank).
+    Reranker,
+    /// One dense vector per image, see [`CanEmbedImage`](super::CanEmbedImage).
+    Image,
+}
+
+impl ModelKind {
+    /// The name of the model kind.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Dense => "dense",
+            Self::Sparse => "sparse",
+            Self::Reranker => "reranker",
+            Self::Image => "image",
+        }
+    }
+}
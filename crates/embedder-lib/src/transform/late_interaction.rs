@@ -0,0 +1,43 @@
+//! Late-interaction scoring of token-level embeddings, as introduced by ColBERT.
+
+use embedder_external::ndarray;
+
+/// The MaxSim score of a document for a query.
+///
+/// For each query token, the similarity of the most similar document token is taken; the score
+/// is the sum over all the query tokens. Both arrays are expected to be of shape
+/// `(tokens, dimensions)` and normalized, so that the dot product is the cosine similarity.
+pub fn maxsim(query: ndarray::ArrayView2<f32>, document: ndarray::ArrayView2<f32>) -> f32 {
+    if document.nrows() == 0 {
+        return 0.0;
+    }
+
+    query
+        .dot(&document.t())
+        .rows()
+        .into_iter()
+        .map(|similarities| {
+            similarities
+                .iter()
+                .copied()
+                .fold(f32::NEG_INFINITY, f32::max)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maxsim_sums_best_matches() {
+        let query = ndarray::arr2(&[[1.0, 0.0], [0.0, 1.0]]);
+        let document = ndarray::arr2(&[[1.0, 0.0], [0.6, 0.8], [-1.0, 0.0]]);
+
+        assert!((maxsim(query.view(), document.view()) - 1.8).abs() <= 1e-6);
+        assert_eq!(
+            maxsim(query.view(), ndarray::Array2::zeros((0, 2)).view()),
+            0.0
+        );
+    }
+}
@@ -3,5 +3,44 @@
 
 pub mod models;
 
+mod bytes;
+pub use bytes::*;
+
+mod dynamic;
+pub use dynamic::*;
+
+mod kind;
+pub use kind::*;
+
+mod late_interaction;
+pub use late_interaction::*;
+
+mod normalization;
+pub use normalization::*;
+
+mod onnx;
+pub use onnx::*;
+
+mod options;
+pub use options::*;
+
+mod pooling;
+pub use pooling::*;
+
+mod prompts;
+pub use prompts::*;
+
+mod rerank;
+pub use rerank::*;
+
+mod sparse;
+pub use sparse::*;
+
+mod tokens;
+pub use tokens::*;
+
 mod traits;
 pub use traits::*;
+
+mod vision;
+pub use vision::*;
@@ -1,20 +1,41 @@
-use crate::transform::traits::CanTransform;
+use super::HasEmbeddedSize;
+use crate::integrity;
+use crate::transform::{
+    traits::CanTransform, ModelBytes, ModelKind, OnnxGraph, OutputSelection, Pooling,
+    PromptTemplates, Tokenizer,
+};
 
 use embedder_err::EmbedderError;
 use embedder_external::fastembed;
+use std::path::Path;
 use std::sync::Arc;
 
 pub struct Model {
-    name: &'static str,
-    output_key: &'static str,
+    name: String,
+    kind: ModelKind,
+    output: OutputSelection,
+    token_output_key: Option<String>,
     model: fastembed::TextEmbedding,
     pooling: Option<fastembed::Pooling>,
+    token_pooling: Option<Pooling>,
+    query_prompt: Option<String>,
+    document_prompt: Option<String>,
+    dimension: Option<usize>,
+    matryoshka_dimensions: Vec<usize>,
+    fingerprint: Option<String>,
 }
 
 impl Model {
     /// Create a new instance of the model.
     ///
     /// To create the model using a folder containing the model files, use the `from_path` method.
+    /// For sparse models, token-level pooling, prompts or session options, use [`ModelBuilder`]
+    /// instead.
+    ///
+    /// The outputs of the ONNX model are read before loading it, so an `output_key` that is not
+    /// one of them fails with the outputs to choose from.
+    ///
+    /// The files are not verified, so the model has no [`CanTransform::fingerprint`].
     pub fn new(
         name: &'static str,
         output_key: &'static str,
@@ -26,31 +47,19 @@ impl Model {
         pooling: Option<fastembed::Pooling>,
         quantization: fastembed::QuantizationMode,
     ) -> Result<Arc<Self>, EmbedderError> {
-        let user_model = fastembed::UserDefinedEmbeddingModel {
-            onnx_file,
-            tokenizer_files: fastembed::TokenizerFiles {
-                tokenizer_file,
-                config_file,
-                special_tokens_map_file,
-                tokenizer_config_file,
-            },
-            pooling: pooling.clone(),
-            quantization,
-        };
-
-        fastembed::TextEmbedding::try_new_from_user_defined(user_model, Default::default())
-            .map_err(|err| EmbedderError::ModelLoadError {
-                name: &name,
-                error: err.to_string(),
-            })
-            .map(|text_embedding| {
-                Arc::new(Self {
-                    name,
-                    output_key,
-                    model: text_embedding,
-                    pooling,
-                })
-            })
+        ModelBuilder::new(name)
+            .output_key(output_key)
+            .pooling(pooling)
+            .quantization(quantization)
+            .load_from_memory(
+                onnx_file,
+                fastembed::TokenizerFiles {
+                    tokenizer_file,
+                    config_file,
+                    special_tokens_map_file,
+                    tokenizer_config_file,
+                },
+            )
     }
 
     /// Create a new instance of the model from a folder containing the model files.
@@ -62,67 +71,370 @@ impl Model {
     /// - `tokenizer_config.json`
     /// - and the model file named by `model_file`, which is commonly `model.onnx`.
     ///
+    /// The folder must also contain a `SHA256SUMS` manifest listing all of the above, as
+    /// produced by `sha256sum`; any file that does not match it is rejected with
+    /// [`EmbedderError::ModelIntegrityError`].
+    ///
     /// Different from ``MODEL_PATH``, the `path` parameter should be the path
     /// to the folder containing the model files, rather than the path to the collection
     /// of models within subfolders.
     ///
-    /// The returned model will be an [`Arc`] to the model.
+    /// The returned model will be an [`Arc`] to the model. See [`ModelBuilder`] for the other
+    /// settings, and for loading models whose name is only known at runtime.
     pub fn from_path(
         name: &'static str,
         output_key: &'static str,
-        path: &std::path::Path,
+        path: &Path,
         model_file: &str,
         pooling: Option<fastembed::Pooling>,
         quantization: fastembed::QuantizationMode,
     ) -> Result<Arc<Self>, EmbedderError> {
-        let path = path.join(name);
+        ModelBuilder::new(name)
+            .output_key(output_key)
+            .model_file(model_file)
+            .pooling(pooling)
+            .quantization(quantization)
+            .load(&path.join(name))
+    }
 
-        let onnx_file =
-            std::fs::read(path.join(model_file)).map_err(|err| EmbedderError::ModelPathError {
-                path: path.to_owned().into(),
-                error: err,
-            })?;
-        let tokenizer_file = std::fs::read(path.join("tokenizer.json")).map_err(|err| {
-            EmbedderError::ModelPathError {
-                path: path.to_owned().into(),
-                error: err,
-            }
-        })?;
-        let config_file = std::fs::read(path.join("config.json")).map_err(|err| {
-            EmbedderError::ModelPathError {
+    /// Create a [`ModelBuilder`] for the model of the given name.
+    pub fn builder(name: impl Into<String>) -> ModelBuilder {
+        ModelBuilder::new(name)
+    }
+}
+
+/// A builder of [`Model`], loading the model from its folder with [`ModelBuilder::load`].
+///
+/// Unlike [`Model::new`], all the settings are owned, so that models discovered at runtime can
+/// be loaded without leaking their names. Without an `output_key`, the output is chosen by
+/// [`OnnxGraph::select_output`]. The files are read from a folder with [`ModelBuilder::load`],
+/// or taken as they are with [`ModelBuilder::load_from_memory`]:
+///
+/// ```ignore
+/// let model = custom::Model::builder("intfloat/e5-small-v2")
+///     .pooling(Some(fastembed::Pooling::Mean))
+///     .query_prompt("query: ")
+///     .document_prompt("passage: ")
+///     .load(Path::new("./models/intfloat/e5-small-v2"))?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct ModelBuilder {
+    name: String,
+    kind: ModelKind,
+    output_key: Option<String>,
+    token_output_key: Option<String>,
+    model_file: String,
+    tokenizer_file: String,
+    config_file: Option<String>,
+    special_tokens_map_file: Option<String>,
+    tokenizer_config_file: String,
+    pooling: Option<fastembed::Pooling>,
+    token_pooling: Option<Pooling>,
+    quantization: fastembed::QuantizationMode,
+    query_prompt: Option<String>,
+    document_prompt: Option<String>,
+    dimension: Option<usize>,
+    matryoshka_dimensions: Vec<usize>,
+    execution_providers: Vec<fastembed::ExecutionProviderDispatch>,
+    max_length: Option<usize>,
+}
+
+impl ModelBuilder {
+    /// Start building a [`ModelKind::Dense`] model of the given name, without pooling,
+    /// quantization or prompts, and with the default file names.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            kind: ModelKind::Dense,
+            output_key: None,
+            token_output_key: None,
+            model_file: "model.onnx".to_owned(),
+            tokenizer_file: "tokenizer.json".to_owned(),
+            config_file: Some("config.json".to_owned()),
+            special_tokens_map_file: Some("special_tokens_map.json".to_owned()),
+            tokenizer_config_file: "tokenizer_config.json".to_owned(),
+            pooling: None,
+            token_pooling: None,
+            quantization: fastembed::QuantizationMode::None,
+            query_prompt: None,
+            document_prompt: None,
+            dimension: None,
+            matryoshka_dimensions: Vec::new(),
+            execution_providers: Vec::new(),
+            max_length: None,
+        }
+    }
+
+    /// The kind of embeddings the model produces.
+    ///
+    /// For SPLADE models, use [`ModelKind::Sparse`] with the MLM logits as the `output_key`.
+    pub fn kind(mut self, kind: ModelKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// The output of the ONNX model to use; by default, the output is detected from the graph,
+    /// see [`OnnxGraph::select_output`].
+    pub fn output_key(mut self, output_key: impl Into<String>) -> Self {
+        self.output_key = Some(output_key.into());
+        self
+    }
+
+    /// The token-level output of the ONNX model, of shape `(batch, tokens, dimensions)`, e.g.
+    /// `token_embeddings`; without it, the pooling cannot be overridden and no token-level
+    /// embeddings are produced.
+    pub fn token_output_key(mut self, token_output_key: impl Into<String>) -> Self {
+        self.token_output_key = Some(token_output_key.into());
+        self
+    }
+
+    /// The ONNX model file, `model.onnx` by default.
+    pub fn model_file(mut self, model_file: impl Into<String>) -> Self {
+        self.model_file = model_file.into();
+        self
+    }
+
+    /// The tokenizer file, `tokenizer.json` by default.
+    pub fn tokenizer_file(mut self, tokenizer_file: impl Into<String>) -> Self {
+        self.tokenizer_file = tokenizer_file.into();
+        self
+    }
+
+    /// The model config, `config.json` by default.
+    pub fn config_file(mut self, config_file: impl Into<String>) -> Self {
+        self.config_file = Some(config_file.into());
+        self
+    }
+
+    /// Load a model without a model config, in which case the padding token id is `0`.
+    pub fn without_config_file(mut self) -> Self {
+        self.config_file = None;
+        self
+    }
+
+    /// The special tokens map, `special_tokens_map.json` by default.
+    pub fn special_tokens_map_file(mut self, special_tokens_map_file: impl Into<String>) -> Self {
+        self.special_tokens_map_file = Some(special_tokens_map_file.into());
+        self
+    }
+
+    /// Load a model without a special tokens map, in which case only the special tokens of the
+    /// tokenizer are used.
+    pub fn without_special_tokens_map_file(mut self) -> Self {
+        self.special_tokens_map_file = None;
+        self
+    }
+
+    /// The tokenizer config, `tokenizer_config.json` by default.
+    ///
+    /// This is required for its `model_max_length` and `pad_token`.
+    pub fn tokenizer_config_file(mut self, tokenizer_config_file: impl Into<String>) -> Self {
+        self.tokenizer_config_file = tokenizer_config_file.into();
+        self
+    }
+
+    /// The pooling of the output by `fastembed`.
+    ///
+    /// For pooling methods not covered by [`fastembed::Pooling`], such as the last-token pooling
+    /// of decoder-based models, set this to `None` and use `token_pooling` instead.
+    pub fn pooling(mut self, pooling: Option<fastembed::Pooling>) -> Self {
+        self.pooling = pooling;
+        self
+    }
+
+    /// The pooling of the token-level output, if not covered by `pooling`.
+    pub fn token_pooling(mut self, token_pooling: Option<Pooling>) -> Self {
+        self.token_pooling = token_pooling;
+        self
+    }
+
+    /// The quantization of the ONNX model.
+    pub fn quantization(mut self, quantization: fastembed::QuantizationMode) -> Self {
+        self.quantization = quantization;
+        self
+    }
+
+    /// The prefix the model expects for queries, e.g. `query: ` for E5 models.
+    pub fn query_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.query_prompt = Some(prompt.into());
+        self
+    }
+
+    /// The prefix the model expects for documents, e.g. `passage: `.
+    pub fn document_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.document_prompt = Some(prompt.into());
+        self
+    }
+
+    /// The embedding dimension, for outputs that do not declare it; if they do, the two must
+    /// agree.
+    pub fn dimension(mut self, dimension: usize) -> Self {
+        self.dimension = Some(dimension);
+        self
+    }
+
+    /// The smaller dimensions the embeddings can be truncated to, for Matryoshka models.
+    pub fn matryoshka_dimensions(mut self, dimensions: impl IntoIterator<Item = usize>) -> Self {
+        self.matryoshka_dimensions = dimensions.into_iter().collect();
+        self
+    }
+
+    /// The execution providers of the ONNX Runtime session, in order of preference; the CPU
+    /// is used if none is available.
+    pub fn execution_providers(
+        mut self,
+        execution_providers: impl IntoIterator<Item = fastembed::ExecutionProviderDispatch>,
+    ) -> Self {
+        self.execution_providers = execution_providers.into_iter().collect();
+        self
+    }
+
+    /// The maximum number of tokens per document, if lower than the `model_max_length` of the
+    /// tokenizer config.
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Load the model from the folder containing its files.
+    ///
+    /// The folder must contain a `SHA256SUMS` manifest listing the files, as described in
+    /// [`Model::from_path`]; the files removed with the `without_*` methods are not needed.
+    pub fn load(self, path: &Path) -> Result<Arc<Model>, EmbedderError> {
+        // Map the files rather than reading them; the mapped pages are backed by the files, so
+        // they can be evicted once verified and handed over.
+        let map = |file_name: &str| {
+            ModelBytes::map(&path.join(file_name)).map_err(|err| EmbedderError::ModelPathError {
                 path: path.to_owned().into(),
                 error: err,
-            }
-        })?;
-        let special_tokens_map_file =
-            std::fs::read(path.join("special_tokens_map.json")).map_err(|err| {
-                EmbedderError::ModelPathError {
-                    path: path.to_owned().into(),
-                    error: err,
+            })
+        };
+        let onnx_file = map(&self.model_file)?;
+        let tokenizer_file = map(&self.tokenizer_file)?;
+        let config_file = self.config_file.as_deref().map(map).transpose()?;
+        let special_tokens_map_file = self
+            .special_tokens_map_file
+            .as_deref()
+            .map(map)
+            .transpose()?;
+        let tokenizer_config_file = map(&self.tokenizer_config_file)?;
+
+        let files = [
+            Some((self.model_file.as_str(), &onnx_file)),
+            Some((self.tokenizer_file.as_str(), &tokenizer_file)),
+            self.config_file.as_deref().zip(config_file.as_ref()),
+            self.special_tokens_map_file
+                .as_deref()
+                .zip(special_tokens_map_file.as_ref()),
+            Some((self.tokenizer_config_file.as_str(), &tokenizer_config_file)),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|(file_name, bytes)| (file_name, &**bytes))
+        .collect::<Vec<_>>();
+
+        let digests = integrity::verify_files(path, &files)?;
+        let fingerprint = integrity::fingerprint(digests.iter().map(String::as_str));
+
+        // A missing JSON file is the same as an empty object to `fastembed`.
+        let or_empty =
+            |bytes: Option<ModelBytes>| bytes.map_or_else(|| b"{}".to_vec(), ModelBytes::into_vec);
+
+        // `fastembed` only takes ownership of the files, so this is the one copy in the heap.
+        self.build(
+            onnx_file.into_vec(),
+            fastembed::TokenizerFiles {
+                tokenizer_file: tokenizer_file.into_vec(),
+                config_file: or_empty(config_file),
+                special_tokens_map_file: or_empty(special_tokens_map_file),
+                tokenizer_config_file: tokenizer_config_file.into_vec(),
+            },
+            Some(fingerprint),
+        )
+    }
+
+    /// Load the model from files already in memory, ignoring the file names of the builder.
+    ///
+    /// The files are not verified, so the model has no [`CanTransform::fingerprint`].
+    pub fn load_from_memory(
+        self,
+        onnx_file: Vec<u8>,
+        tokenizer_files: fastembed::TokenizerFiles,
+    ) -> Result<Arc<Model>, EmbedderError> {
+        self.build(onnx_file, tokenizer_files, None)
+    }
+
+    /// Create the model from its files, with the fingerprint of the files if they were verified.
+    ///
+    /// The output is chosen by [`OnnxGraph::select_output`], and the token-level output checked
+    /// by [`OnnxGraph::token_output`], before the session is created; a configured `dimension`
+    /// must match the one declared by the output.
+    fn build(
+        self,
+        onnx_file: Vec<u8>,
+        tokenizer_files: fastembed::TokenizerFiles,
+        fingerprint: Option<String>,
+    ) -> Result<Arc<Model>, EmbedderError> {
+        let output = OnnxGraph::parse(&onnx_file)
+            .map_err(|err| err.to_string())
+            .and_then(|graph| {
+                if let Some(key) = &self.token_output_key {
+                    graph.token_output(key)?;
                 }
-            })?;
-        let tokenizer_config_file =
-            std::fs::read(path.join("tokenizer_config.json")).map_err(|err| {
-                EmbedderError::ModelPathError {
-                    path: path.to_owned().into(),
-                    error: err,
+
+                let output = graph.select_output(self.kind, self.output_key.as_deref())?;
+                match (output.dimension, self.dimension) {
+                    (Some(declared), Some(dimension)) if declared != dimension => Err(format!(
+                        "the output '{key}' has {declared} dimensions, not {dimension}.",
+                        key = output.key
+                    )),
+                    _ => Ok(output),
                 }
+            })
+            .map_err(|error| EmbedderError::ModelLoadError {
+                name: self.name.clone(),
+                error,
             })?;
 
-        Self::new(
-            name,
-            output_key,
+        let mut options = fastembed::InitOptionsUserDefined::default();
+        options.execution_providers = self.execution_providers;
+        if let Some(max_length) = self.max_length {
+            options.max_length = max_length;
+        }
+
+        let user_model = fastembed::UserDefinedEmbeddingModel {
             onnx_file,
-            tokenizer_file,
-            config_file,
-            special_tokens_map_file,
-            tokenizer_config_file,
-            pooling,
-            quantization,
-        )
+            tokenizer_files,
+            pooling: self.pooling.clone(),
+            quantization: self.quantization,
+        };
+
+        match fastembed::TextEmbedding::try_new_from_user_defined(user_model, options) {
+            Ok(text_embedding) => Ok(Arc::new(Model {
+                name: self.name,
+                kind: self.kind,
+                output,
+                token_output_key: self.token_output_key,
+                model: text_embedding,
+                pooling: self.pooling,
+                token_pooling: self.token_pooling,
+                query_prompt: self.query_prompt,
+                document_prompt: self.document_prompt,
+                dimension: self.dimension,
+                matryoshka_dimensions: self.matryoshka_dimensions,
+                fingerprint,
+            })),
+            Err(err) => Err(EmbedderError::ModelLoadError {
+                name: self.name,
+                error: err.to_string(),
+            }),
+        }
     }
 }
 
+/// The files are loaded at runtime, so nothing is embedded.
+impl HasEmbeddedSize for Arc<Model> {}
+
 impl CanTransform for Arc<Model> {
     /// The name of the model.
     fn name(&self) -> &str {
@@ -130,8 +442,33 @@ impl CanTransform for Arc<Model> {
     }
 
     /// The output key of the model.
-    fn output_key(&self) -> &'static str {
-        self.output_key
+    fn output_key(&self) -> &str {
+        &self.output.key
+    }
+
+    /// The output of the model, and whether it was configured or detected.
+    fn output_selection(&self) -> Option<OutputSelection> {
+        Some(self.output.clone())
+    }
+
+    /// The token-level output key of the model, if it was set.
+    fn token_output_key(&self) -> Option<&str> {
+        self.token_output_key.as_deref()
+    }
+
+    /// The embedding dimension declared by the output of the model, or else the configured one.
+    fn dimension(&self) -> Option<usize> {
+        self.output.dimension.or(self.dimension)
+    }
+
+    /// The dimensions the embeddings can be truncated to.
+    fn matryoshka_dimensions(&self) -> Vec<usize> {
+        self.matryoshka_dimensions.clone()
+    }
+
+    /// The kind of embeddings the model produces.
+    fn kind(&self) -> ModelKind {
+        self.kind
     }
 
     /// The pooling method used by the model.
@@ -139,6 +476,29 @@ impl CanTransform for Arc<Model> {
         self.pooling.clone()
     }
 
+    /// The pooling of the token-level output, if not covered by `pooling`.
+    fn token_pooling(&self) -> Option<Pooling> {
+        self.token_pooling
+    }
+
+    /// The prefixes the model expects for queries and documents.
+    fn prompts(&self) -> PromptTemplates<'_> {
+        PromptTemplates {
+            query: self.query_prompt.as_deref(),
+            document: self.document_prompt.as_deref(),
+        }
+    }
+
+    /// The fingerprint of the model files, if they were verified.
+    fn fingerprint(&self) -> Option<String> {
+        self.fingerprint.clone()
+    }
+
+    /// The tokenizer used by the model.
+    fn tokenizer(&self) -> &Tokenizer {
+        &self.model.tokenizer
+    }
+
     fn transform<'e, 'r, 's, S: AsRef<str> + Send + Sync>(
         &'e self,
         texts: Vec<S>,
@@ -189,6 +549,8 @@ mod test {
             .embed_to_array(documents.clone(), None)
             .expect("Failed to embed the documents.");
 
+        assert!(model.fingerprint().is_some());
+        assert_eq!(model.dimension(), Some(384));
         assert_eq!(embeddings.shape()[0], documents.len());
         assert_eq!(embeddings.shape()[1], 384);
 
@@ -208,4 +570,46 @@ mod test {
                 );
             });
     }
+
+    #[cfg(feature = "sentence_transformers_all_minilm_l6_v2")]
+    #[test]
+    fn load_all_mini_l6_v2_with_builder() {
+        use crate::transform::OutputSource;
+
+        let model_path = std::path::PathBuf::from(test_utils::get_model_path());
+        let name = ["sentence-transformers", "all-MiniLM-L6-v2"].join("/");
+
+        let model = super::Model::builder(name.clone())
+            .pooling(Some(fastembed::Pooling::Mean))
+            .query_prompt(String::from("query: "))
+            .execution_providers([])
+            .load(&model_path.join(&name))
+            .expect("Failed to load the model.");
+
+        assert_eq!(model.name(), name);
+        assert_eq!(model.output_key(), "sentence_embedding");
+        assert_eq!(model.prompts().query, Some("query: "));
+        assert!(model.fingerprint().is_some());
+        assert_eq!(
+            model.output_selection(),
+            Some(OutputSelection {
+                key: "sentence_embedding".to_owned(),
+                source: OutputSource::Detected,
+                dimension: Some(384),
+            })
+        );
+
+        let embeddings = model
+            .embed_to_array(vec!["Hello, world!"], None)
+            .expect("Failed to embed the documents.");
+
+        assert!((embeddings.sum() - 0.5960535).abs() <= 1e-4);
+    }
+
+    #[test]
+    fn builder_requires_model_files() {
+        let result = super::Model::builder("missing").load(Path::new("./does-not-exist"));
+
+        assert!(matches!(result, Err(EmbedderError::ModelPathError { .. })));
+    }
 }
@@ -0,0 +1,405 @@
+//! Macros for declaring the embedded models.
+//!
+//! The invocations are generated by `build.rs` from `models.toml`, for the enabled models only.
+//!
+//! Modules using [`create_model!`] need `binaries`, `CanTransform`, `EmbeddedSize`,
+//! `HasEmbeddedSize`, `ModelBytes`, `ModelKind`, `Pooling`, `PromptTemplates`, `Tokenizer`,
+//! `EmbedderError`, `fastembed`, `Arc` and `OnceLock` in scope; modules using [`create_reranker!`] need
+//! `CanRerank` instead of `CanTransform`, and modules using [`create_image_model!`] need
+//! `CanEmbedImage`, `ImagePreprocessor`, `ModelSession`, `load_session` and `ort`.
+
+/// Implement [`HasEmbeddedSize`](super::HasEmbeddedSize) for the model of a module, from the
+/// sizes recorded by `build.rs` in its `binaries`.
+macro_rules! impl_embedded_size {
+    ($binaries: ident) => {
+        impl HasEmbeddedSize for Arc<Model> {
+            /// The size of the embedded model files.
+            fn embedded_size(&self) -> Option<EmbeddedSize> {
+                Some(EmbeddedSize {
+                    compressed: binaries::$binaries::COMPRESSED_SIZE,
+                    decompressed: binaries::$binaries::DECOMPRESSED_SIZE,
+                })
+            }
+        }
+    };
+}
+
+/// Declare a singleton model embedded in the binary.
+macro_rules! create_model {
+    (
+        module: $module: ident,
+        name: $name: literal,
+        binaries: $binaries: ident,
+        kind: $kind: expr,
+        output_key: $output_key: literal,
+        token_output_key: $token_output_key: expr,
+        pooling: $pooling: expr,
+        token_pooling: $token_pooling: expr,
+        quantization: $quantization: expr,
+        prompts: $prompts: expr,
+        dimension: $dimension: expr,
+        matryoshka_dimensions: [$($matryoshka_dimensions: literal),*$(,)?]
+        $(,)?
+    ) => {
+        pub mod $module {
+            use super::*;
+
+            static MODEL: OnceLock<Result<Arc<Model>, EmbedderError>> = OnceLock::new();
+
+            #[doc = "Singleton struct for the `"]
+            #[doc = $name]
+            #[doc = "` model."]
+            pub struct Model {
+                model: fastembed::TextEmbedding,
+            }
+
+            impl Model {
+                const NAME: &'static str = $name;
+
+                #[cfg(test)]
+                pub fn embed_with_fastembed<S: AsRef<str> + Send + Sync>(
+                    &self,
+                    texts: Vec<S>,
+                    batch_size: Option<usize>,
+                ) -> Result<Vec<fastembed::Embedding>, EmbedderError> {
+                    self.model
+                        .embed(texts, batch_size)
+                        .map_err(|err| EmbedderError::FastEmbedError(err))
+                }
+
+                /// Create a new instance of the model.
+                pub fn new() -> Result<Arc<Self>, EmbedderError> {
+                    Self::singleton().map(Arc::clone)
+                }
+
+                /// Get the singleton instance of the model, loading it on its first use.
+                ///
+                /// The reference coerces to `&'static dyn DynEmbedder` without another
+                /// allocation.
+                pub fn singleton() -> Result<&'static Arc<Self>, EmbedderError> {
+                    match MODEL.get_or_init(|| {
+                        let load = |bytes| {
+                            binaries::load(bytes)
+                                .map(ModelBytes::into_vec)
+                                .map_err(|err| EmbedderError::ModelLoadError {
+                                    name: Self::NAME.to_owned(),
+                                    error: err.to_string(),
+                                })
+                        };
+                        let user_model = fastembed::UserDefinedEmbeddingModel {
+                            onnx_file: load(binaries::$binaries::MODEL_FILE)?,
+                            tokenizer_files: fastembed::TokenizerFiles {
+                                tokenizer_file: load(binaries::$binaries::TOKENIZER_FILE)?,
+                                config_file: load(binaries::$binaries::CONFIG_FILE)?,
+                                special_tokens_map_file: load(
+                                    binaries::$binaries::SPECIAL_TOKENS_MAP_FILE,
+                                )?,
+                                tokenizer_config_file: load(
+                                    binaries::$binaries::TOKENIZER_CONFIG_FILE,
+                                )?,
+                            },
+                            pooling: $pooling,
+                            quantization: $quantization,
+                        };
+
+                        fastembed::TextEmbedding::try_new_from_user_defined(
+                            user_model,
+                            Default::default(),
+                        )
+                        .map_err(|err| EmbedderError::FastEmbedError(err))
+                        .map(|text_embedding| {
+                            Arc::new(Self {
+                                model: text_embedding,
+                            })
+                        })
+                    }) {
+                        &Ok(ref model) => Ok(model),
+                        &Err(ref err) => Err(EmbedderError::ModelLoadError {
+                            name: Self::NAME.to_owned(),
+                            error: err.to_string(),
+                        }),
+                    }
+                }
+            }
+
+            impl CanTransform for Arc<Model> {
+                /// The name of the model.
+                fn name(&self) -> &str {
+                    Model::NAME
+                }
+
+                /// The output key of the model.
+                fn output_key(&self) -> &str {
+                    $output_key
+                }
+
+                /// The kind of embeddings the model produces.
+                fn kind(&self) -> ModelKind {
+                    $kind
+                }
+
+                /// The token-level output key of the model.
+                fn token_output_key(&self) -> Option<&str> {
+                    $token_output_key
+                }
+
+                /// The tokenizer used by the model.
+                fn tokenizer(&self) -> &Tokenizer {
+                    &self.model.tokenizer
+                }
+
+                /// The pooling method used by the model.
+                fn pooling(&self) -> Option<fastembed::Pooling> {
+                    $pooling
+                }
+
+                /// The pooling of the token-level output, if not covered by `pooling`.
+                fn token_pooling(&self) -> Option<Pooling> {
+                    $token_pooling
+                }
+
+                /// The prefixes the model expects for queries and documents.
+                fn prompts(&self) -> PromptTemplates<'_> {
+                    $prompts
+                }
+
+                /// The number of dimensions of the embeddings.
+                fn dimension(&self) -> Option<usize> {
+                    $dimension
+                }
+
+                /// The sizes the embeddings can be truncated to.
+                fn matryoshka_dimensions(&self) -> Vec<usize> {
+                    vec![$($matryoshka_dimensions),*]
+                }
+
+                /// The fingerprint of the embedded model files.
+                fn fingerprint(&self) -> Option<String> {
+                    Some(binaries::$binaries::FINGERPRINT.to_owned())
+                }
+
+                /// Transforms the input texts into embeddings.
+                fn transform<'e, 'r, 's, S: AsRef<str> + Send + Sync>(
+                    &'e self,
+                    texts: Vec<S>,
+                    batch_size: Option<usize>,
+                ) -> Result<fastembed::EmbeddingOutput<'r, 's>, EmbedderError>
+                where
+                    'e: 'r,
+                    'e: 's,
+                {
+                    self.model
+                        .transform(texts, batch_size)
+                        .map_err(|err| EmbedderError::FastEmbedError(err))
+                }
+            }
+
+            impl_embedded_size!($binaries);
+        }
+    };
+}
+
+/// Declare a singleton cross-encoder reranking model embedded in the binary.
+macro_rules! create_reranker {
+    (
+        module: $module: ident,
+        name: $name: literal,
+        binaries: $binaries: ident
+        $(,)?
+    ) => {
+        pub mod $module {
+            use super::*;
+
+            static MODEL: OnceLock<Result<Arc<Model>, EmbedderError>> = OnceLock::new();
+
+            #[doc = "Singleton struct for the `"]
+            #[doc = $name]
+            #[doc = "` reranking model."]
+            pub struct Model {
+                model: fastembed::TextRerank,
+            }
+
+            impl Model {
+                const NAME: &'static str = $name;
+
+                /// Create a new instance of the model.
+                pub fn new() -> Result<Arc<Self>, EmbedderError> {
+                    match MODEL.get_or_init(|| {
+                        let load = |bytes| {
+                            binaries::load(bytes)
+                                .map(ModelBytes::into_vec)
+                                .map_err(|err| EmbedderError::ModelLoadError {
+                                    name: Self::NAME.to_owned(),
+                                    error: err.to_string(),
+                                })
+                        };
+                        let user_model = fastembed::UserDefinedRerankingModel {
+                            onnx_file: load(binaries::$binaries::MODEL_FILE)?,
+                            tokenizer_files: fastembed::TokenizerFiles {
+                                tokenizer_file: load(binaries::$binaries::TOKENIZER_FILE)?,
+                                config_file: load(binaries::$binaries::CONFIG_FILE)?,
+                                special_tokens_map_file: load(
+                                    binaries::$binaries::SPECIAL_TOKENS_MAP_FILE,
+                                )?,
+                                tokenizer_config_file: load(
+                                    binaries::$binaries::TOKENIZER_CONFIG_FILE,
+                                )?,
+                            },
+                        };
+
+                        fastembed::TextRerank::try_new_from_user_defined(
+                            user_model,
+                            Default::default(),
+                        )
+                        .map_err(|err| EmbedderError::FastEmbedError(err))
+                        .map(|text_rerank| Arc::new(Self { model: text_rerank }))
+                    }) {
+                        &Ok(ref model) => Ok(Arc::clone(model)),
+                        &Err(ref err) => Err(EmbedderError::ModelLoadError {
+                            name: Self::NAME.to_owned(),
+                            error: err.to_string(),
+                        }),
+                    }
+                }
+            }
+
+            impl CanRerank for Arc<Model> {
+                /// The name of the model.
+                fn name(&self) -> &str {
+                    Model::NAME
+                }
+
+                /// The tokenizer used by the model.
+                fn tokenizer(&self) -> &Tokenizer {
+                    &self.model.tokenizer
+                }
+
+                /// The fingerprint of the embedded model files.
+                fn fingerprint(&self) -> Option<String> {
+                    Some(binaries::$binaries::FINGERPRINT.to_owned())
+                }
+
+                /// Score each of the documents for their relevance to the query.
+                fn rerank<S: AsRef<str> + Send + Sync>(
+                    &self,
+                    query: S,
+                    documents: Vec<S>,
+                    batch_size: Option<usize>,
+                ) -> Result<Vec<f32>, EmbedderError> {
+                    let mut scores = vec![0.0; documents.len()];
+
+                    self.model
+                        .rerank(query, documents, false, batch_size)
+                        .map_err(|err| EmbedderError::FastEmbedError(err))?
+                        .into_iter()
+                        .for_each(|result| scores[result.index] = result.score);
+
+                    Ok(scores)
+                }
+            }
+
+            impl_embedded_size!($binaries);
+        }
+    };
+}
+
+/// Declare a singleton vision encoder embedded in the binary.
+macro_rules! create_image_model {
+    (
+        module: $module: ident,
+        name: $name: literal,
+        binaries: $binaries: ident,
+        output_key: $output_key: literal,
+        text_model: $text_model: expr
+        $(,)?
+    ) => {
+        pub mod $module {
+            use super::*;
+
+            /// The name of the text model sharing the vector space with this model.
+            pub const TEXT_MODEL: Option<&str> = $text_model;
+
+            static MODEL: OnceLock<Result<Arc<Model>, EmbedderError>> = OnceLock::new();
+
+            #[doc = "Singleton struct for the `"]
+            #[doc = $name]
+            #[doc = "` image model."]
+            pub struct Model {
+                session: ModelSession,
+                preprocessor: ImagePreprocessor,
+            }
+
+            impl Model {
+                const NAME: &'static str = $name;
+
+                /// Create a new instance of the model.
+                pub fn new() -> Result<Arc<Self>, EmbedderError> {
+                    match MODEL.get_or_init(|| {
+                        let load = |bytes| {
+                            binaries::load(bytes).map_err(|err| EmbedderError::ModelLoadError {
+                                name: Self::NAME.to_owned(),
+                                error: err.to_string(),
+                            })
+                        };
+                        let preprocessor = ImagePreprocessor::from_config(&load(
+                            binaries::$binaries::PREPROCESSOR_CONFIG_FILE,
+                        )?)
+                        .map_err(|error| EmbedderError::ModelLoadError {
+                            name: Self::NAME.to_owned(),
+                            error,
+                        })?;
+
+                        load_session(load(binaries::$binaries::MODEL_FILE)?)
+                            .map_err(|err| EmbedderError::FastEmbedError(err.into()))
+                            .map(|session| {
+                                Arc::new(Self {
+                                    session,
+                                    preprocessor,
+                                })
+                            })
+                    }) {
+                        &Ok(ref model) => Ok(Arc::clone(model)),
+                        &Err(ref err) => Err(EmbedderError::ModelLoadError {
+                            name: Self::NAME.to_owned(),
+                            error: err.to_string(),
+                        }),
+                    }
+                }
+            }
+
+            impl CanEmbedImage for Arc<Model> {
+                /// The name of the model.
+                fn name(&self) -> &str {
+                    Model::NAME
+                }
+
+                /// The key of the pooled image embeddings output.
+                fn output_key(&self) -> &'static str {
+                    $output_key
+                }
+
+                /// The name of the text model sharing the vector space with this model.
+                fn text_model(&self) -> Option<&'static str> {
+                    TEXT_MODEL
+                }
+
+                /// The preprocessing of the images.
+                fn preprocessor(&self) -> &ImagePreprocessor {
+                    &self.preprocessor
+                }
+
+                /// The ONNX Runtime session of the vision encoder.
+                fn session(&self) -> &ort::Session {
+                    &self.session
+                }
+
+                /// The fingerprint of the embedded model files.
+                fn fingerprint(&self) -> Option<String> {
+                    Some(binaries::$binaries::FINGERPRINT.to_owned())
+                }
+            }
+
+            impl_embedded_size!($binaries);
+        }
+    };
+}
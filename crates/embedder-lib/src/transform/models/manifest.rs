@@ -0,0 +1,293 @@
+//! Loading the models of a `models.toml` manifest at runtime.
+//!
+//! This reads the same manifest as `build.rs`, but loads each model from its folder with
+//! [`custom::ModelBuilder`] instead of embedding its files, so that a build without any of
+//! the model features can still serve them. Only `dense` and `sparse` models can be loaded this
+//! way. The values of the keys are parsed by the same functions as in `build.rs`.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use embedder_err::EmbedderError;
+use embedder_external::{fastembed, toml};
+
+use super::custom;
+use crate::transform::{ModelKind, Pooling};
+
+// Shared with `build.rs`, which parses the embedded models with the same functions.
+#[path = "../../manifest_keys.rs"]
+mod manifest_keys;
+use manifest_keys::{ModelPooling, Quantization, TokenPooling, TEXT_MODEL_FILES};
+
+/// The manifest of the embedded models, as read by `build.rs`.
+///
+/// This is the default manifest of the models to load at runtime.
+pub const EMBEDDED_MANIFEST: &str = include_str!("../../../models.toml");
+
+/// A model declared in the manifest.
+#[derive(Debug, Clone)]
+pub struct ModelEntry {
+    name: String,
+    table: toml::Table,
+}
+
+impl ModelEntry {
+    /// The name of the model, as used in the requests.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The cargo feature embedding the model, if it has any.
+    pub fn feature(&self) -> Option<&str> {
+        self.get_str("feature").ok().flatten()
+    }
+
+    /// The `kind` of the model, as written in the manifest.
+    pub fn kind(&self) -> &str {
+        self.get_str("kind").ok().flatten().unwrap_or("dense")
+    }
+
+    /// An error in this entry of the manifest.
+    fn invalid(&self, message: impl std::fmt::Display) -> EmbedderError {
+        EmbedderError::ModelManifestError {
+            name: self.name.clone(),
+            error: message.to_string(),
+        }
+    }
+
+    /// The string value of the key, if present.
+    fn get_str(&self, key: &str) -> Result<Option<&str>, EmbedderError> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(toml::Value::String(value)) => Ok(Some(value)),
+            Some(_) => Err(self.invalid(format!("`{key}` must be a string."))),
+        }
+    }
+
+    /// The integer values of the key; a single integer is taken as a list of one.
+    fn get_integers(&self, key: &str) -> Result<Vec<usize>, EmbedderError> {
+        let integer = |value: &toml::Value| {
+            value
+                .as_integer()
+                .and_then(|value| usize::try_from(value).ok())
+                .filter(|value| *value > 0)
+                .ok_or_else(|| self.invalid(format!("`{key}` must only hold positive integers.")))
+        };
+
+        match self.table.get(key) {
+            None => Ok(Vec::new()),
+            Some(value @ toml::Value::Integer(_)) => integer(value).map(|value| vec![value]),
+            Some(toml::Value::Array(values)) => values.iter().map(integer).collect(),
+            Some(_) => {
+                Err(self.invalid(format!("`{key}` must be an integer or a list of integers.")))
+            }
+        }
+    }
+
+    /// The files of the model within its folder, in the order of [`TEXT_MODEL_FILES`].
+    fn files(&self) -> Result<[&str; TEXT_MODEL_FILES.len()], EmbedderError> {
+        match self.table.get("files") {
+            None => Ok(TEXT_MODEL_FILES),
+            Some(toml::Value::Array(files)) if files.len() == TEXT_MODEL_FILES.len() => {
+                let mut file_names = TEXT_MODEL_FILES;
+                for (file_name, file) in file_names.iter_mut().zip(files) {
+                    *file_name = file
+                        .as_str()
+                        .ok_or_else(|| self.invalid("`files` must be strings."))?;
+                }
+                Ok(file_names)
+            }
+            Some(_) => Err(self.invalid(format!(
+                "`files` must list {count} files for a {kind} model.",
+                count = TEXT_MODEL_FILES.len(),
+                kind = self.kind()
+            ))),
+        }
+    }
+
+    /// Load the model from its folder within `model_dir`, i.e. `<model_dir>/<name>`.
+    ///
+    /// The folder is verified against its `SHA256SUMS` manifest like any other custom model.
+    pub fn load(&self, model_dir: &Path) -> Result<Arc<custom::Model>, EmbedderError> {
+        let kind = match self.kind() {
+            "dense" => ModelKind::Dense,
+            "sparse" => ModelKind::Sparse,
+            kind => {
+                return Err(self.invalid(format!(
+                    "'{kind}' models cannot be loaded at runtime; enable their feature instead."
+                )))
+            }
+        };
+        let pooling = manifest_keys::parse_pooling(self.get_str("pooling")?)
+            .map_err(|message| self.invalid(message))?
+            .map(|pooling| match pooling {
+                ModelPooling::Mean => fastembed::Pooling::Mean,
+                ModelPooling::Cls => fastembed::Pooling::Cls,
+            });
+        let token_pooling = manifest_keys::parse_token_pooling(self.get_str("token_pooling")?)
+            .map_err(|message| self.invalid(message))?
+            .map(|pooling| match pooling {
+                TokenPooling::Mean => Pooling::Mean,
+                TokenPooling::Cls => Pooling::Cls,
+                TokenPooling::Max => Pooling::Max,
+                TokenPooling::LastToken => Pooling::LastToken,
+                TokenPooling::WeightedMean => Pooling::WeightedMean,
+            });
+        let quantization = match manifest_keys::parse_quantization(self.get_str("quantization")?)
+            .map_err(|message| self.invalid(message))?
+        {
+            Quantization::None => fastembed::QuantizationMode::None,
+            Quantization::Static => fastembed::QuantizationMode::Static,
+            Quantization::Dynamic => fastembed::QuantizationMode::Dynamic,
+        };
+        let [model, tokenizer, config, special_tokens_map, tokenizer_config] = self.files()?;
+        let builder = custom::Model::builder(self.name.as_str())
+            .kind(kind)
+            .model_file(model)
+            .tokenizer_file(tokenizer)
+            .config_file(config)
+            .special_tokens_map_file(special_tokens_map)
+            .tokenizer_config_file(tokenizer_config)
+            .pooling(pooling)
+            .token_pooling(token_pooling)
+            .quantization(quantization)
+            .matryoshka_dimensions(self.get_integers("matryoshka_dimensions")?);
+        // Without an `output_key`, the output is detected from the ONNX graph.
+        let builder = match self.get_str("output_key")? {
+            Some(output_key) => builder.output_key(output_key),
+            None => builder,
+        };
+        let builder = match self.get_str("token_output_key")? {
+            Some(token_output_key) => builder.token_output_key(token_output_key),
+            None => builder,
+        };
+        let builder = match self.get_integers("dimension")?.as_slice() {
+            [] => builder,
+            [dimension] => builder.dimension(*dimension),
+            _ => return Err(self.invalid("`dimension` must be a single integer.")),
+        };
+        let builder = match self.get_str("query_prompt")? {
+            Some(prompt) => builder.query_prompt(prompt),
+            None => builder,
+        };
+        let builder = match self.get_str("document_prompt")? {
+            Some(prompt) => builder.document_prompt(prompt),
+            None => builder,
+        };
+
+        builder.load(&model_dir.join(&self.name))
+    }
+}
+
+/// Parse the `[[model]]` entries of a manifest.
+pub fn parse_manifest(contents: &str) -> Result<Vec<ModelEntry>, EmbedderError> {
+    let invalid = |error: String| EmbedderError::ModelManifestError {
+        name: "model".to_owned(),
+        error,
+    };
+    let manifest: toml::Table = contents.parse().map_err(|err| invalid(format!("{err}")))?;
+
+    match manifest.get("model") {
+        None => Ok(Vec::new()),
+        Some(toml::Value::Array(models)) => models
+            .iter()
+            .map(|model| match model {
+                toml::Value::Table(table) => match table.get("name") {
+                    Some(toml::Value::String(name)) => Ok(ModelEntry {
+                        name: name.clone(),
+                        table: table.clone(),
+                    }),
+                    _ => Err(invalid("each model needs a `name` string.".to_owned())),
+                },
+                _ => Err(invalid("each `[[model]]` must be a table.".to_owned())),
+            })
+            .collect(),
+        Some(_) => Err(invalid("`model` must be an array of tables.".to_owned())),
+    }
+}
+
+/// Read the `[[model]]` entries of the manifest at `path`.
+pub fn read_manifest(path: &Path) -> Result<Vec<ModelEntry>, EmbedderError> {
+    std::fs::read_to_string(path)
+        .map_err(|err| EmbedderError::ModelPathError {
+            path: path.into(),
+            error: err,
+        })
+        .and_then(|contents| parse_manifest(&contents))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_embedded_manifest() {
+        let entries = parse_manifest(EMBEDDED_MANIFEST).expect("Failed to parse the manifest.");
+
+        let minilm = entries
+            .iter()
+            .find(|entry| entry.name() == "sentence-transformers/all-MiniLM-L6-v2")
+            .expect("MiniLM is not in the manifest.");
+        assert_eq!(minilm.kind(), "dense");
+        assert_eq!(
+            minilm.feature(),
+            Some("sentence_transformers_all_minilm_l6_v2")
+        );
+        assert_eq!(minilm.files().ok(), Some(TEXT_MODEL_FILES));
+
+        let reranker = entries
+            .iter()
+            .find(|entry| entry.name() == "BAAI/bge-reranker-base")
+            .expect("The reranker is not in the manifest.");
+        assert!(matches!(
+            reranker.load(Path::new("./models")),
+            Err(EmbedderError::ModelManifestError { .. })
+        ));
+    }
+
+    #[test]
+    fn parse_entry_keys() {
+        let entries = parse_manifest(
+            r#"
+            [[model]]
+            name = "nomic-ai/nomic-embed-text-v1.5"
+            files = [
+                "onnx/model.onnx",
+                "tokenizer.json",
+                "config.json",
+                "special_tokens_map.json",
+                "tokenizer_config.json",
+            ]
+            dimension = 768
+            matryoshka_dimensions = [512, 256]
+
+            [[model]]
+            name = "BAAI/bge-small-en-v1.5"
+            files = ["onnx/model.onnx"]
+            pooling = "max"
+            "#,
+        )
+        .expect("Failed to parse the manifest.");
+
+        let nomic = &entries[0];
+        assert_eq!(
+            nomic.files().ok().map(|files| files[0]),
+            Some("onnx/model.onnx")
+        );
+        assert_eq!(nomic.get_integers("dimension").ok(), Some(vec![768]));
+        assert_eq!(
+            nomic.get_integers("matryoshka_dimensions").ok(),
+            Some(vec![512, 256])
+        );
+
+        let bge = &entries[1];
+        assert!(matches!(
+            bge.files(),
+            Err(EmbedderError::ModelManifestError { .. })
+        ));
+        assert!(matches!(
+            bge.load(Path::new("./models")),
+            Err(EmbedderError::ModelManifestError { error, .. }) if error.contains("`pooling`")
+        ));
+    }
+}
@@ -0,0 +1,490 @@
+//! A deterministic mock model, which needs no model files.
+//!
+//! The embeddings are derived from the SHA-256 of each text, so that the same text always
+//! produces the same embedding, regardless of the batch or the platform. They carry no meaning
+//! whatsoever; this is only meant for tests and the local development of clients.
+//!
+//! The token-level embeddings are derived from the text of each token in the same way, so
+//! documents sharing words also share token embeddings for late-interaction scoring. The
+//! tokenizer has no vocabulary though; every word is tokenized into `[UNK]`.
+//!
+//! The pseudo-embeddings are passed through a minimal ONNX graph built in memory, so that
+//! [`CanTransform::transform`] returns a genuine [`fastembed::EmbeddingOutput`] and the mock goes
+//! through the same pooling, truncation and normalization as any other model.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use embedder_err::EmbedderError;
+use embedder_external::sha2::{Digest, Sha256};
+use embedder_external::{fastembed, ndarray, ort, tokenizers};
+
+use super::HasEmbeddedSize;
+use crate::transform::{tokenize, traits::CanTransform, Tokenizer};
+
+/// The name of the model, as used in the requests.
+pub const NAME: &str = "mock";
+
+static MODEL: OnceLock<Result<Arc<Model>, EmbedderError>> = OnceLock::new();
+
+/// The behaviour of a mock [`Model`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockOptions {
+    /// The number of dimensions of the embeddings.
+    pub dimension: usize,
+
+    /// The time each call to the model takes, simulating the inference.
+    pub latency: Duration,
+
+    /// Fail any call with a text containing this marker, as if the inference failed.
+    pub fail_on: Option<String>,
+
+    /// The maximum number of tokens of each text, including `[CLS]` and `[SEP]`.
+    pub max_input_length: usize,
+}
+
+impl Default for MockOptions {
+    fn default() -> Self {
+        Self {
+            dimension: 384,
+            latency: Duration::ZERO,
+            fail_on: None,
+            max_input_length: 512,
+        }
+    }
+}
+
+/// Parse an environment variable, if it is set and not empty.
+fn env_var<T>(key: &str) -> Result<Option<T>, EmbedderError>
+where
+    T: FromStr,
+    <T as FromStr>::Err: std::fmt::Display,
+{
+    match std::env::var(key) {
+        Ok(value) if !value.is_empty() => {
+            value
+                .parse()
+                .map(Some)
+                .map_err(|error: <T as FromStr>::Err| EmbedderError::EnvVarError {
+                    key: key.to_owned(),
+                    error: error.to_string(),
+                })
+        }
+        _ => Ok(None),
+    }
+}
+
+impl MockOptions {
+    /// Read the options from the `MOCK_DIMENSION`, `MOCK_LATENCY_MS`, `MOCK_FAIL_ON` and
+    /// `MOCK_MAX_INPUT_LENGTH` environment variables, defaulting to [`MockOptions::default`].
+    pub fn from_env() -> Result<Self, EmbedderError> {
+        let default = Self::default();
+
+        Ok(Self {
+            dimension: env_var("MOCK_DIMENSION")?.unwrap_or(default.dimension),
+            latency: env_var("MOCK_LATENCY_MS")?
+                .map(Duration::from_millis)
+                .unwrap_or(default.latency),
+            fail_on: env_var("MOCK_FAIL_ON")?.or(default.fail_on),
+            max_input_length: env_var("MOCK_MAX_INPUT_LENGTH")?.unwrap_or(default.max_input_length),
+        })
+    }
+}
+
+/// The pseudo-embedding of a text, uniformly distributed in `[-1, 1]` in each dimension.
+fn hash_embedding(text: &str, dimension: usize) -> impl Iterator<Item = f32> + '_ {
+    (0u32..)
+        .flat_map(move |block| {
+            Sha256::new()
+                .chain_update(block.to_le_bytes())
+                .chain_update(text.as_bytes())
+                .finalize()
+                .chunks_exact(4)
+                .map(|bytes| {
+                    let value = u32::from_le_bytes(bytes.try_into().unwrap_or_default());
+                    (value as f64 / u32::MAX as f64 * 2.0 - 1.0) as f32
+                })
+                .collect::<Vec<_>>()
+        })
+        .take(dimension)
+}
+
+/// Build the tokenizer of the mock model, which splits on whitespaces and punctuations.
+fn build_tokenizer(max_input_length: usize) -> tokenizers::Result<Tokenizer> {
+    let special_tokens = [("[PAD]", 0), ("[UNK]", 1), ("[CLS]", 2), ("[SEP]", 3)];
+
+    let model = tokenizers::models::wordlevel::WordLevel::builder()
+        .vocab(
+            special_tokens
+                .iter()
+                .map(|(token, id)| (token.to_string(), *id))
+                .collect::<HashMap<_, _>>(),
+        )
+        .unk_token("[UNK]".to_owned())
+        .build()?;
+    let post_processor = tokenizers::processors::template::TemplateProcessing::builder()
+        .try_single("[CLS] $A [SEP]")?
+        .special_tokens(vec![("[CLS]", 2), ("[SEP]", 3)])
+        .build()?;
+
+    let mut tokenizer = Tokenizer::new(model.into());
+    tokenizer
+        .with_pre_tokenizer(tokenizers::pre_tokenizers::whitespace::Whitespace)
+        .with_post_processor(post_processor)
+        .with_decoder(tokenizers::decoders::wordpiece::WordPiece::default())
+        .with_truncation(Some(tokenizers::TruncationParams {
+            max_length: max_input_length,
+            ..Default::default()
+        }))?;
+
+    Ok(tokenizer)
+}
+
+/// The ONNX graph of the mock model, which passes the pseudo-embeddings through as its outputs.
+mod graph {
+    use crate::transform::onnx::proto::*;
+
+    /// The input of the token-level embeddings, of shape `(batch_size, sequence_length, dim)`.
+    pub const TOKEN_INPUT: &str = "token_input";
+
+    /// The input of the sentence embeddings, of shape `(batch_size, dim)`.
+    pub const SENTENCE_INPUT: &str = "sentence_input";
+
+    /// The `float` tensor `name` of the given dimensions, either fixed or symbolic.
+    fn tensor(name: &str, dims: &[Result<usize, &str>]) -> ValueInfoProto {
+        ValueInfoProto {
+            name: name.to_owned(),
+            r#type: Some(TypeProto {
+                tensor_type: Some(TensorTypeProto {
+                    elem_type: 1,
+                    shape: Some(TensorShapeProto {
+                        dim: dims
+                            .iter()
+                            .map(|dim| DimensionProto {
+                                dim_value: dim.ok().map(|size| size as i64),
+                                dim_param: dim.err().map(str::to_owned),
+                            })
+                            .collect(),
+                    }),
+                }),
+            }),
+        }
+    }
+
+    /// Encode the graph passing `token_input` through as `token_embeddings`, and
+    /// `sentence_input` as `sentence_embedding`.
+    pub fn encode(dimension: usize) -> Vec<u8> {
+        let tokens = [Err("batch_size"), Err("sequence_length"), Ok(dimension)];
+        let sentences = [Err("batch_size"), Ok(dimension)];
+        let identity = |input: &str, output: &str| NodeProto {
+            input: vec![input.to_owned()],
+            output: vec![output.to_owned()],
+            name: output.to_owned(),
+            op_type: "Identity".to_owned(),
+        };
+
+        prost::Message::encode_to_vec(&ModelProto {
+            ir_version: 8,
+            producer_name: super::NAME.to_owned(),
+            graph: Some(GraphProto {
+                node: vec![
+                    identity(TOKEN_INPUT, "token_embeddings"),
+                    identity(SENTENCE_INPUT, "sentence_embedding"),
+                ],
+                name: super::NAME.to_owned(),
+                input: vec![
+                    tensor(TOKEN_INPUT, &tokens),
+                    tensor(SENTENCE_INPUT, &sentences),
+                ],
+                output: vec![
+                    tensor("token_embeddings", &tokens),
+                    tensor("sentence_embedding", &sentences),
+                ],
+                ..Default::default()
+            }),
+            opset_import: vec![OperatorSetIdProto {
+                domain: String::new(),
+                version: 13,
+            }],
+            ..Default::default()
+        })
+    }
+}
+
+/// A mock model producing deterministic pseudo-embeddings, see the module documentation.
+pub struct Model {
+    options: MockOptions,
+    tokenizer: Tokenizer,
+    session: ort::Session,
+}
+
+impl Model {
+    /// Get the singleton instance of the model, configured by [`MockOptions::from_env`].
+    pub fn new() -> Result<Arc<Self>, EmbedderError> {
+        Self::singleton().map(Arc::clone)
+    }
+
+    /// Get a reference to the singleton instance of the model, see [`Model::new`].
+    pub fn singleton() -> Result<&'static Arc<Self>, EmbedderError> {
+        Self::get_or_init(MockOptions::from_env)
+    }
+
+    /// Create the singleton instance with the given options instead of the environment, e.g. in
+    /// tests; fails if it was already created with other options.
+    pub fn init(options: MockOptions) -> Result<&'static Arc<Self>, EmbedderError> {
+        let model = Self::get_or_init(|| Ok(options.clone()))?;
+
+        match model.options == options {
+            true => Ok(model),
+            false => Err(EmbedderError::ModelLoadError {
+                name: NAME.to_owned(),
+                error: format!("the model was already created with {:?}.", model.options),
+            }),
+        }
+    }
+
+    /// Get the singleton instance, creating it with the `options` if it does not exist yet.
+    fn get_or_init(
+        options: impl FnOnce() -> Result<MockOptions, EmbedderError>,
+    ) -> Result<&'static Arc<Self>, EmbedderError> {
+        match MODEL.get_or_init(|| options().and_then(Self::with_options)) {
+            Ok(model) => Ok(model),
+            Err(err) => Err(EmbedderError::ModelLoadError {
+                name: NAME.to_owned(),
+                error: err.to_string(),
+            }),
+        }
+    }
+
+    /// Create a new instance of the model with the given options, outside of the singleton.
+    pub fn with_options(options: MockOptions) -> Result<Arc<Self>, EmbedderError> {
+        let load_error = |error: String| EmbedderError::ModelLoadError {
+            name: NAME.to_owned(),
+            error,
+        };
+
+        let tokenizer =
+            build_tokenizer(options.max_input_length).map_err(|err| load_error(err.to_string()))?;
+        let session = ort::Session::builder()
+            .and_then(|builder| builder.commit_from_memory(&graph::encode(options.dimension)))
+            .map_err(|err| load_error(err.to_string()))?;
+
+        Ok(Arc::new(Self {
+            options,
+            tokenizer,
+            session,
+        }))
+    }
+
+    /// The options of the model.
+    pub fn options(&self) -> &MockOptions {
+        &self.options
+    }
+
+    /// The text of each token of the texts, which [`hash_embedding`] turns into its embedding;
+    /// the special tokens are taken as they are.
+    fn token_words<S: AsRef<str>>(&self, texts: &[S]) -> Result<Vec<Vec<String>>, EmbedderError> {
+        Ok(tokenize(&self.tokenizer, texts, true)?
+            .iter()
+            .zip(texts)
+            .map(|(document, text)| {
+                document
+                    .tokens
+                    .iter()
+                    .map(|token| match token.special {
+                        true => token.token.clone(),
+                        false => text
+                            .as_ref()
+                            .chars()
+                            .skip(token.offsets.0)
+                            .take(token.offsets.1 - token.offsets.0)
+                            .collect(),
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Run the pseudo-embeddings of a batch through the session, see [`graph`].
+    fn run_batch<S: AsRef<str>>(
+        &self,
+        texts: &[S],
+    ) -> Result<fastembed::SingleBatchOutput<'_, '_>, EmbedderError> {
+        let ort_error = |err: ort::Error| EmbedderError::FastEmbedError(err.into());
+
+        let dimension = self.options.dimension;
+        let words = self.token_words(texts)?;
+        let length = words.iter().map(Vec::len).max().unwrap_or_default();
+
+        // The documents are padded to the longest one, as the attention mask records.
+        let mut token_input = ndarray::Array3::<f32>::zeros((texts.len(), length, dimension));
+        let mut attention_mask_array = ndarray::Array2::<i64>::zeros((texts.len(), length));
+        for (row, words) in words.iter().enumerate() {
+            for (column, word) in words.iter().enumerate() {
+                token_input
+                    .slice_mut(ndarray::s![row, column, ..])
+                    .assign(&ndarray::Array1::from_iter(hash_embedding(word, dimension)));
+                attention_mask_array[[row, column]] = 1;
+            }
+        }
+        let sentence_input = ndarray::Array2::from_shape_vec(
+            (texts.len(), dimension),
+            texts
+                .iter()
+                .flat_map(|text| hash_embedding(text.as_ref(), dimension))
+                .collect(),
+        )
+        .map_err(|err| EmbedderError::OutputTransformError(err.to_string()))?;
+
+        let inputs = ort::inputs![
+            graph::TOKEN_INPUT => ort::Value::from_array(token_input).map_err(ort_error)?,
+            graph::SENTENCE_INPUT => ort::Value::from_array(sentence_input).map_err(ort_error)?,
+        ]
+        .map_err(ort_error)?;
+
+        Ok(fastembed::SingleBatchOutput {
+            session_outputs: self.session.run(inputs).map_err(ort_error)?,
+            attention_mask_array,
+        })
+    }
+
+    /// Wait for the simulated latency, then fail if any of the texts contains the marker.
+    fn simulate<S: AsRef<str>>(&self, texts: &[S]) -> Result<(), EmbedderError> {
+        if !self.options.latency.is_zero() {
+            std::thread::sleep(self.options.latency);
+        }
+
+        match &self.options.fail_on {
+            Some(marker) => texts
+                .iter()
+                .position(|text| text.as_ref().contains(marker.as_str()))
+                .map_or(Ok(()), |index| {
+                    Err(EmbedderError::FastEmbedError(anyhow::Error::msg(format!(
+                        "Injected failure for document #{index} of '{NAME}'."
+                    ))))
+                }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The mock model has no files at all.
+impl HasEmbeddedSize for Arc<Model> {}
+
+impl CanTransform for Arc<Model> {
+    /// The name of the model.
+    fn name(&self) -> &str {
+        NAME
+    }
+
+    /// The output key of the model, which is only nominal.
+    fn output_key(&self) -> &str {
+        "sentence_embedding"
+    }
+
+    /// The token-level output key of the model, which is only nominal.
+    fn token_output_key(&self) -> Option<&str> {
+        Some("token_embeddings")
+    }
+
+    /// The tokenizer used by the model.
+    fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
+
+    /// The embeddings are already pooled.
+    fn pooling(&self) -> Option<fastembed::Pooling> {
+        None
+    }
+
+    /// The number of dimensions of the embeddings.
+    fn dimension(&self) -> Option<usize> {
+        Some(self.options.dimension)
+    }
+
+    /// Run the pseudo-embeddings through the session, after the simulated latency and failure.
+    fn transform<'e, 'r, 's, S: AsRef<str> + Send + Sync>(
+        &'e self,
+        texts: Vec<S>,
+        batch_size: Option<usize>,
+    ) -> Result<fastembed::EmbeddingOutput<'r, 's>, EmbedderError>
+    where
+        'e: 'r,
+        'e: 's,
+    {
+        self.simulate(&texts)?;
+
+        texts
+            .chunks(batch_size.unwrap_or(texts.len()).max(1))
+            .map(|batch| self.run_batch(batch))
+            .collect::<Result<Vec<_>, _>>()
+            .map(fastembed::EmbeddingOutput::new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deterministic_embeddings() {
+        let model = Model::with_options(MockOptions {
+            dimension: 16,
+            ..Default::default()
+        })
+        .expect("Failed to create the mock model.");
+
+        let embeddings = model
+            .embed_to_array(vec!["Hello, world!", "Foo Bar", "Hello, world!"], None)
+            .expect("Failed to embed the documents.");
+
+        assert_eq!(embeddings.shape(), &[3, 16]);
+        assert_eq!(embeddings.row(0), embeddings.row(2));
+        assert_ne!(embeddings.row(0), embeddings.row(1));
+        embeddings.rows().into_iter().for_each(|row| {
+            assert!((row.dot(&row) - 1.0).abs() <= 1e-4);
+        });
+
+        let again = model
+            .embed_to_array(vec!["Foo Bar"], None)
+            .expect("Failed to embed the documents.");
+        assert_eq!(again.row(0), embeddings.row(1));
+    }
+
+    #[test]
+    fn token_embeddings() {
+        let model = Model::with_options(MockOptions {
+            dimension: 8,
+            max_input_length: 4,
+            ..Default::default()
+        })
+        .expect("Failed to create the mock model.");
+
+        let tokens = model
+            .embed_to_token_arrays(vec!["foo bar", "bar baz qux"], None)
+            .expect("Failed to embed the tokens.");
+
+        // `[CLS] foo bar [SEP]`, and `[CLS] bar baz [SEP]` once truncated
+        assert_eq!(tokens[0].shape(), &[4, 8]);
+        assert_eq!(tokens[1].shape(), &[4, 8]);
+        assert_eq!(tokens[0].row(2), tokens[1].row(1));
+        assert_eq!(model.max_input_length(), Some(4));
+    }
+
+    #[test]
+    fn injected_failure() {
+        let model = Model::with_options(MockOptions {
+            fail_on: Some("__fail__".to_owned()),
+            ..Default::default()
+        })
+        .expect("Failed to create the mock model.");
+
+        assert!(model.embed_to_array(vec!["fine"], None).is_ok());
+        assert!(matches!(
+            model.embed_to_array(vec!["fine", "this will __fail__"], None),
+            Err(EmbedderError::FastEmbedError(_))
+        ));
+    }
+}
@@ -1,23 +1,87 @@
 //! Declaration of all the models supported by the library.
 
+#[macro_use]
+mod macros;
+
 pub mod custom;
 
-mod sentence_transformers;
-pub use sentence_transformers::*;
+pub mod manifest;
+
+#[cfg(feature = "mock")]
+pub mod mock;
+
+/// The modules of the models declared in `models.toml`, see `build.rs`.
+#[allow(unused_imports)]
+mod generated {
+    use super::{binaries, EmbeddedSize, HasEmbeddedSize};
+    use crate::transform::{
+        load_session, traits::CanTransform, CanEmbedImage, CanRerank, ImagePreprocessor,
+        ModelBytes, ModelKind, ModelSession, Pooling, PromptTemplates, Tokenizer,
+    };
+
+    use embedder_err::EmbedderError;
+    use embedder_external::{fastembed, ort};
+    use std::sync::{Arc, OnceLock};
+
+    include!(concat!(
+        env!("OUT_DIR"),
+        "/src/transform/models/generated.rs"
+    ));
+}
+pub use generated::*;
+
+/// The size of the files of a model embedded in the binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbeddedSize {
+    /// The number of bytes embedded in the binary, which is the same as `decompressed` unless
+    /// the `zstd` feature is enabled.
+    pub compressed: usize,
+    /// The number of bytes of the model files once loaded.
+    pub decompressed: usize,
+}
+
+/// The size of the embedded files, shared by all the model traits, e.g.
+/// [`CanTransform`](crate::transform::CanTransform).
+pub trait HasEmbeddedSize {
+    /// The compressed and decompressed size of the model files embedded in the binary.
+    ///
+    /// `None` for the models loaded at runtime.
+    fn embedded_size(&self) -> Option<EmbeddedSize> {
+        None
+    }
+}
 
 /// Embedded models for the transformers.
 mod binaries {
+    use crate::transform::ModelBytes;
+
     include!(concat!(
         env!("OUT_DIR"),
         "/src/transform/models/binaries.rs"
     ));
+
+    /// Load an embedded file, decompressing it if it was compressed by `build.rs`.
+    // No models may be embedded in this build, leaving this unused.
+    #[allow(dead_code)]
+    #[cfg(feature = "zstd")]
+    pub fn load(bytes: &'static [u8]) -> std::io::Result<ModelBytes> {
+        zstd::decode_all(bytes).map(ModelBytes::Owned)
+    }
+
+    /// Load an embedded file, which is borrowed as is without the `zstd` feature.
+    #[allow(dead_code)]
+    #[cfg(not(feature = "zstd"))]
+    pub fn load(bytes: &'static [u8]) -> std::io::Result<ModelBytes> {
+        Ok(ModelBytes::Static(bytes))
+    }
 }
 
 /// Basic value and dimension verification tests for each model.
 #[cfg(test)]
 #[allow(unused_imports)]
 mod tests {
-    use crate::transform::{self, CanTransform};
+    use crate::transform::{self, CanEmbedImage, CanRerank, CanTransform};
+    use embedder_external::ndarray;
 
     use std::time::Instant;
 
@@ -82,12 +146,19 @@ mod tests {
                     dim=embeddings.shape()[1],
                 );
 
-                let expected = $expected;
+                // The sums are printed in full so that a new model can have them recorded.
+                let expected: &[f32] = $expected;
+                assert_eq!(
+                    expected.len(),
+                    TEST_DOCUMENTS.len(),
+                    "The reference sums are not recorded; found {sums:?}.",
+                    sums=embeddings.sum_axis(ndarray::Axis(1)).to_vec(),
+                );
 
                 embeddings.rows().into_iter().zip(embeddings_with_fastembed).enumerate()
                 .for_each(
                     |(id, (embedding, fastembed))| {
-                        let expected = expected.get(id).expect("Expected embedding not found.");
+                        let expected = expected[id];
                         let actual = embedding.sum();
 
                         assert!(
@@ -146,4 +217,314 @@ mod tests {
             0.0506501_f32,
         ],
     );
+
+    #[test]
+    #[cfg(feature = "sentence_transformers_all_minilm_l6_v2")]
+    fn test_minilm_l6_v2_token_arrays() {
+        let model =
+            transform::models::all_minilm_l6_v2::Model::new().expect("Could not load the model.");
+
+        let tokenized = model
+            .tokenize(TEST_DOCUMENTS, true)
+            .expect("Could not tokenize the documents.");
+        let token_arrays = model
+            .embed_to_token_arrays(TEST_DOCUMENTS.to_vec(), None)
+            .expect("Could not perform transformation.");
+        let embeddings = model
+            .embed_to_pooled_array(TEST_DOCUMENTS.to_vec(), None)
+            .expect("Could not perform transformation.");
+
+        assert_eq!(token_arrays.len(), TEST_DOCUMENTS.len());
+
+        token_arrays
+            .iter()
+            .zip(tokenized.iter())
+            .zip(embeddings.rows())
+            .enumerate()
+            .for_each(|(id, ((tokens, document), embedding))| {
+                assert_eq!(
+                    tokens.shape()[0],
+                    document.len(),
+                    "Mismatch in token count for document #{id}."
+                );
+
+                // Mean pooling of the token embeddings should reproduce the sentence embedding.
+                let mean = tokens
+                    .mean_axis(ndarray::Axis(0))
+                    .expect("Document has no tokens.");
+                mean.iter().zip(embedding.iter()).enumerate().for_each(
+                    |(fid, (actual, expected))| {
+                        assert!(
+                            (actual - expected).abs() <= EPS,
+                            "Mismatch for document #{id} feature #{fid}; expected {expected}, found {actual}.",
+                        )
+                    },
+                );
+            });
+    }
+
+    #[test]
+    #[cfg(feature = "sentence_transformers_all_minilm_l6_v2")]
+    fn test_minilm_l6_v2_embed_with_options() {
+        let model =
+            transform::models::all_minilm_l6_v2::Model::new().expect("Could not load the model.");
+
+        let expected = model
+            .embed_to_array(TEST_DOCUMENTS.to_vec(), None)
+            .expect("Could not perform transformation.");
+
+        // Mean pooling of the token embeddings is what the model does by default.
+        [
+            transform::EmbedOptions::default(),
+            transform::EmbedOptions {
+                pooling: Some(transform::Pooling::Mean),
+                ..Default::default()
+            },
+        ]
+        .iter()
+        .for_each(|options| {
+            let actual = model
+                .embed_with_options(TEST_DOCUMENTS.to_vec(), None, options)
+                .expect("Could not perform transformation.");
+
+            assert_eq!(actual.shape(), expected.shape());
+            actual
+                .iter()
+                .zip(expected.iter())
+                .for_each(|(actual, expected)| {
+                    assert!(
+                        (actual - expected).abs() <= EPS,
+                        "Mismatch for {options:?}; expected {expected}, found {actual}.",
+                    )
+                });
+        });
+
+        let l1 = model
+            .embed_with_options(
+                TEST_DOCUMENTS.to_vec(),
+                None,
+                &transform::EmbedOptions {
+                    normalization: transform::Normalization::L1,
+                    ..Default::default()
+                },
+            )
+            .expect("Could not perform transformation.");
+
+        l1.rows().into_iter().for_each(|row| {
+            let norm = row.map(|v| v.abs()).sum();
+            assert!((norm - 1.0).abs() <= EPS, "L1 norm is {norm}.");
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "snowflake_snowflake_arctic_embed_m_v1_5")]
+    fn test_snowflake_arctic_embed_m_v1_5_matryoshka() {
+        let model = transform::models::snowflake_arctic_embed_m_v1_5::Model::new()
+            .expect("Could not load the model.");
+
+        assert_eq!(model.matryoshka_dimensions(), vec![768, 256]);
+
+        let full = model
+            .embed_with_options(
+                TEST_DOCUMENTS.to_vec(),
+                None,
+                &transform::EmbedOptions {
+                    normalization: transform::Normalization::None,
+                    ..Default::default()
+                },
+            )
+            .expect("Could not perform transformation.");
+        let truncated = model
+            .embed_with_options(
+                TEST_DOCUMENTS.to_vec(),
+                None,
+                &transform::EmbedOptions {
+                    dimensions: Some(256),
+                    ..Default::default()
+                },
+            )
+            .expect("Could not perform transformation.");
+
+        assert_eq!(truncated.shape(), &[TEST_DOCUMENTS.len(), 256]);
+
+        // The truncated embeddings are the prefixes of the full ones, normalized again.
+        let expected =
+            transform::Normalization::L2.apply(full.slice(ndarray::s![.., ..256]).to_owned());
+        truncated
+            .iter()
+            .zip(expected.iter())
+            .for_each(|(actual, expected)| {
+                assert!(
+                    (actual - expected).abs() <= EPS,
+                    "Expected {expected}, found {actual}.",
+                )
+            });
+
+        let error = model
+            .embed_with_options(
+                TEST_DOCUMENTS.to_vec(),
+                None,
+                &transform::EmbedOptions {
+                    dimensions: Some(128),
+                    ..Default::default()
+                },
+            )
+            .expect_err("An undeclared size should be rejected.");
+        assert!(
+            matches!(
+                error,
+                embedder_err::EmbedderError::UnsupportedDimensions {
+                    dimensions: 128,
+                    ..
+                }
+            ),
+            "Unexpected error: {error:?}"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "prithivida_splade_pp_en_v1")]
+    fn test_splade_pp_en_v1() {
+        let model =
+            transform::models::splade_pp_en_v1::Model::new().expect("Could not load the model.");
+
+        let embeddings = model
+            .embed_to_sparse(TEST_DOCUMENTS.to_vec(), None)
+            .expect("Could not perform transformation.");
+
+        assert_eq!(embeddings.len(), TEST_DOCUMENTS.len());
+
+        embeddings.iter().enumerate().for_each(|(id, embedding)| {
+            assert!(!embedding.is_empty(), "Document #{id} has no terms.");
+            assert_eq!(embedding.indices.len(), embedding.values.len());
+            assert!(
+                embedding.indices.windows(2).all(|pair| pair[0] < pair[1]),
+                "Indices of document #{id} are not sorted."
+            );
+            assert!(embedding.values.iter().all(|value| *value > 0.0));
+
+            // The most important terms of a document should include some of its own words.
+            let terms = model.decode_terms(&embedding.indices);
+            assert!(
+                terms
+                    .iter()
+                    .any(|term| TEST_DOCUMENTS[id].to_lowercase().contains(term.as_str())),
+                "None of the terms of document #{id} appear in the document.",
+            );
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "baai_bge_reranker_base")]
+    fn test_bge_reranker_base() {
+        let model =
+            transform::models::bge_reranker_base::Model::new().expect("Could not load the model.");
+
+        let scores = model
+            .rerank(
+                "What is the name of the Rust formatting tool?",
+                TEST_DOCUMENTS.to_vec(),
+                Some(3),
+            )
+            .expect("Could not rerank the documents.");
+
+        assert_eq!(scores.len(), TEST_DOCUMENTS.len());
+
+        // The document about Rustfmt should be the most relevant.
+        let best = scores
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(idx, _)| idx);
+        assert_eq!(best, Some(7), "Unexpected scores: {scores:?}");
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "qdrant_clip_vit_b_32_text",
+        feature = "qdrant_clip_vit_b_32_vision"
+    ))]
+    fn test_clip_vit_b_32() {
+        use embedder_external::image;
+
+        let text_model = transform::models::clip_vit_b_32_text::Model::new()
+            .expect("Could not load the text model.");
+        let image_model = transform::models::clip_vit_b_32_vision::Model::new()
+            .expect("Could not load the image model.");
+
+        assert_eq!(image_model.text_model(), Some(text_model.name()));
+
+        let colours = [
+            ("red", [255, 0, 0]),
+            ("green", [0, 255, 0]),
+            ("blue", [0, 0, 255]),
+        ];
+        let images = colours
+            .iter()
+            .map(|(_, rgb)| {
+                image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+                    320,
+                    240,
+                    image::Rgb(*rgb),
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let image_embeddings = image_model
+            .embed_decoded_images(&images, Some(2))
+            .map(|array| transform::Normalization::L2.apply(array))
+            .expect("Could not embed the images.");
+        let text_embeddings = text_model
+            .embed_to_array(
+                colours
+                    .iter()
+                    .map(|(name, _)| format!("a plain {name} square"))
+                    .collect(),
+                None,
+            )
+            .expect("Could not embed the texts.");
+
+        assert_eq!(image_embeddings.shape(), &[colours.len(), 512]);
+        assert_eq!(text_embeddings.shape(), image_embeddings.shape());
+
+        // Each image should be closest to the description of its own colour.
+        let similarities = image_embeddings.dot(&text_embeddings.t());
+        similarities
+            .rows()
+            .into_iter()
+            .enumerate()
+            .for_each(|(id, row)| {
+                let best = row
+                    .iter()
+                    .enumerate()
+                    .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .map(|(idx, _)| idx);
+                assert_eq!(best, Some(id), "Unexpected similarities: {similarities:?}");
+            });
+    }
+
+    #[test]
+    #[cfg(all(feature = "zstd", feature = "sentence_transformers_all_minilm_l6_v2"))]
+    fn test_zstd_embedded_size() {
+        use super::binaries::{self, sentence_transformers_all_minilm_l6_v2 as files};
+
+        let embedded = [
+            files::MODEL_FILE,
+            files::TOKENIZER_FILE,
+            files::CONFIG_FILE,
+            files::SPECIAL_TOKENS_MAP_FILE,
+            files::TOKENIZER_CONFIG_FILE,
+        ];
+        let loaded = embedded
+            .iter()
+            .map(|bytes| binaries::load(bytes).map(|bytes| bytes.len()))
+            .sum::<std::io::Result<usize>>()
+            .expect("Could not decompress the embedded files.");
+
+        assert_eq!(
+            embedded.iter().map(|bytes| bytes.len()).sum::<usize>(),
+            files::COMPRESSED_SIZE
+        );
+        assert_eq!(loaded, files::DECOMPRESSED_SIZE);
+    }
 }
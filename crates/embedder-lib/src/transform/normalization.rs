@@ -0,0 +1,50 @@
+//! Normalization of the pooled embeddings.
+
+use embedder_external::ndarray;
+use embedder_external::serde::Deserialize;
+
+const EPS: f32 = 1e-12;
+
+/// Normalization methods that can be requested per call.
+///
+/// Deserialized from the [`Normalization::name`] of each method.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    /// Scale each embedding to unit Euclidean length.
+    #[default]
+    L2,
+    /// Scale each embedding such that the absolute values sum to one.
+    L1,
+    /// Return the pooled embeddings as they are.
+    None,
+}
+
+impl Normalization {
+    /// The name of the normalization method.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::L2 => "l2",
+            Self::L1 => "l1",
+            Self::None => "none",
+        }
+    }
+
+    /// Normalize each row of the array.
+    pub fn apply(&self, mut array: ndarray::Array2<f32>) -> ndarray::Array2<f32> {
+        let norm = match self {
+            Self::L2 => |row: ndarray::ArrayView1<f32>| row.map(|v| v.powi(2)).sum().sqrt(),
+            Self::L1 => |row: ndarray::ArrayView1<f32>| row.map(|v| v.abs()).sum(),
+            Self::None => return array,
+        };
+
+        array.rows_mut().into_iter().for_each(|mut row| {
+            let norm = norm(row.view());
+            for val in row.iter_mut() {
+                *val /= norm + EPS;
+            }
+        });
+
+        array
+    }
+}
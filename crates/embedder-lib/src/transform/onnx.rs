@@ -0,0 +1,555 @@
+//! Reading the inputs and outputs of an ONNX model without creating a session.
+//!
+//! Only the few messages of `onnx.proto` describing the graph are decoded; the attributes of
+//! the nodes and the weights are skipped, so this is cheap even for large models.
+
+use embedder_err::EmbedderError;
+
+use super::ModelKind;
+
+/// The subset of the messages of `onnx.proto` needed to describe a model, and to write the
+/// graph of the mock model.
+pub(crate) mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ModelProto {
+        #[prost(int64, tag = "1")]
+        pub ir_version: i64,
+        #[prost(string, tag = "2")]
+        pub producer_name: String,
+        #[prost(string, tag = "3")]
+        pub producer_version: String,
+        #[prost(message, optional, tag = "7")]
+        pub graph: Option<GraphProto>,
+        #[prost(message, repeated, tag = "8")]
+        pub opset_import: Vec<OperatorSetIdProto>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct OperatorSetIdProto {
+        #[prost(string, tag = "1")]
+        pub domain: String,
+        #[prost(int64, tag = "2")]
+        pub version: i64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GraphProto {
+        #[prost(message, repeated, tag = "1")]
+        pub node: Vec<NodeProto>,
+        #[prost(string, tag = "2")]
+        pub name: String,
+        #[prost(message, repeated, tag = "5")]
+        pub initializer: Vec<TensorProto>,
+        #[prost(message, repeated, tag = "11")]
+        pub input: Vec<ValueInfoProto>,
+        #[prost(message, repeated, tag = "12")]
+        pub output: Vec<ValueInfoProto>,
+    }
+
+    /// The attributes are not decoded.
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct NodeProto {
+        #[prost(string, repeated, tag = "1")]
+        pub input: Vec<String>,
+        #[prost(string, repeated, tag = "2")]
+        pub output: Vec<String>,
+        #[prost(string, tag = "3")]
+        pub name: String,
+        #[prost(string, tag = "4")]
+        pub op_type: String,
+    }
+
+    /// Only the name is decoded, so that the weights are not copied.
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TensorProto {
+        #[prost(string, tag = "8")]
+        pub name: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ValueInfoProto {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(message, optional, tag = "2")]
+        pub r#type: Option<TypeProto>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TypeProto {
+        #[prost(message, optional, tag = "1")]
+        pub tensor_type: Option<TensorTypeProto>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TensorTypeProto {
+        #[prost(int32, tag = "1")]
+        pub elem_type: i32,
+        #[prost(message, optional, tag = "2")]
+        pub shape: Option<TensorShapeProto>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TensorShapeProto {
+        #[prost(message, repeated, tag = "1")]
+        pub dim: Vec<DimensionProto>,
+    }
+
+    /// `dim_value` and `dim_param` are a `oneof` in `onnx.proto`.
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct DimensionProto {
+        #[prost(int64, optional, tag = "1")]
+        pub dim_value: Option<i64>,
+        #[prost(string, optional, tag = "2")]
+        pub dim_param: Option<String>,
+    }
+}
+
+/// The outputs a dense model is expected to have, in order of preference, if no output key is
+/// given.
+///
+/// `sentence_embedding` is already pooled by the model; the others are token-level and pooled
+/// as configured.
+pub const DENSE_OUTPUT_PRECEDENCE: [&str; 3] = [
+    "sentence_embedding",
+    "last_hidden_state",
+    "token_embeddings",
+];
+
+/// The outputs a sparse model is expected to have, in order of preference, if no output key is
+/// given.
+pub const SPARSE_OUTPUT_PRECEDENCE: [&str; 1] = ["logits"];
+
+/// A dimension of a tensor in the graph.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OnnxDimension {
+    /// A dimension of fixed size.
+    Fixed(usize),
+    /// A dimension of variable size, named by the model, e.g. `batch_size`.
+    Symbolic(String),
+    /// A dimension of variable size without a name.
+    Unknown,
+}
+
+impl std::fmt::Display for OnnxDimension {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fixed(size) => write!(f, "{size}"),
+            Self::Symbolic(name) => write!(f, "{name}"),
+            Self::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+/// An input or output of the graph.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OnnxTensor {
+    pub name: String,
+    /// The element type, as named in `onnx.proto`, e.g. `float`.
+    pub element_type: &'static str,
+    /// The dimensions of the tensor; `None` if the model does not declare its shape.
+    pub shape: Option<Vec<OnnxDimension>>,
+}
+
+impl OnnxTensor {
+    /// The size of the last dimension, which is the embedding dimension of an output.
+    pub fn last_dimension(&self) -> Option<usize> {
+        match self.shape.as_ref()?.last()? {
+            OnnxDimension::Fixed(size) => Some(*size),
+            _ => None,
+        }
+    }
+
+    /// The shape as written in the errors, e.g. `(batch_size, sequence_length, 384)`.
+    pub fn shape_name(&self) -> String {
+        match &self.shape {
+            Some(shape) => format!(
+                "({})",
+                shape
+                    .iter()
+                    .map(OnnxDimension::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            None => "(unknown shape)".to_owned(),
+        }
+    }
+
+    fn from_proto(value: proto::ValueInfoProto) -> Self {
+        let tensor_type = value.r#type.and_then(|value_type| value_type.tensor_type);
+
+        Self {
+            name: value.name,
+            element_type: element_type_name(
+                tensor_type.as_ref().map_or(0, |tensor| tensor.elem_type),
+            ),
+            shape: tensor_type.and_then(|tensor| tensor.shape).map(|shape| {
+                shape
+                    .dim
+                    .into_iter()
+                    .map(|dim| match (dim.dim_value, dim.dim_param) {
+                        (Some(size), _) if size > 0 => OnnxDimension::Fixed(size as usize),
+                        (_, Some(name)) if !name.is_empty() => OnnxDimension::Symbolic(name),
+                        _ => OnnxDimension::Unknown,
+                    })
+                    .collect()
+            }),
+        }
+    }
+}
+
+/// The name of a `TensorProto.DataType`.
+fn element_type_name(elem_type: i32) -> &'static str {
+    match elem_type {
+        1 => "float",
+        2 => "uint8",
+        3 => "int8",
+        4 => "uint16",
+        5 => "int16",
+        6 => "int32",
+        7 => "int64",
+        8 => "string",
+        9 => "bool",
+        10 => "float16",
+        11 => "double",
+        12 => "uint32",
+        13 => "uint64",
+        14 => "complex64",
+        15 => "complex128",
+        16 => "bfloat16",
+        _ => "undefined",
+    }
+}
+
+/// An operator set the model was exported with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OnnxOpset {
+    /// The domain of the operators; empty for the default `ai.onnx` domain.
+    pub domain: String,
+    pub version: i64,
+}
+
+/// How the output of a model was chosen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputSource {
+    /// The output key was given when loading the model.
+    Configured,
+    /// The output was chosen by [`OnnxGraph::select_output`].
+    Detected,
+}
+
+impl OutputSource {
+    /// The name of the source, as shown in `/models`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Configured => "configured",
+            Self::Detected => "detected",
+        }
+    }
+}
+
+/// The output of a model used for its embeddings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputSelection {
+    pub key: String,
+    pub source: OutputSource,
+    /// The embedding dimension, if the output declares a fixed one.
+    pub dimension: Option<usize>,
+}
+
+/// The description of an ONNX model.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OnnxGraph {
+    pub ir_version: i64,
+    /// The exporter of the model, e.g. `pytorch 2.1.0`.
+    pub producer: String,
+    pub opsets: Vec<OnnxOpset>,
+    /// The inputs to feed, excluding the weights that older exporters list as inputs.
+    pub inputs: Vec<OnnxTensor>,
+    pub outputs: Vec<OnnxTensor>,
+}
+
+impl OnnxGraph {
+    /// Read the description of an ONNX model from its file.
+    pub fn parse(bytes: &[u8]) -> Result<Self, EmbedderError> {
+        let model = <proto::ModelProto as prost::Message>::decode(bytes)
+            .map_err(|err| EmbedderError::OnnxModelError(err.to_string()))?;
+        let graph = model
+            .graph
+            .ok_or_else(|| EmbedderError::OnnxModelError("the model has no graph.".to_owned()))?;
+
+        let weights = graph
+            .initializer
+            .iter()
+            .map(|tensor| tensor.name.as_str())
+            .collect::<std::collections::HashSet<_>>();
+
+        Ok(Self {
+            ir_version: model.ir_version,
+            producer: [model.producer_name, model.producer_version]
+                .into_iter()
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join(" "),
+            opsets: model
+                .opset_import
+                .into_iter()
+                .map(|opset| OnnxOpset {
+                    domain: opset.domain,
+                    version: opset.version,
+                })
+                .collect(),
+            inputs: graph
+                .input
+                .iter()
+                .filter(|input| !weights.contains(input.name.as_str()))
+                .cloned()
+                .map(OnnxTensor::from_proto)
+                .collect(),
+            outputs: graph
+                .output
+                .into_iter()
+                .map(OnnxTensor::from_proto)
+                .collect(),
+        })
+    }
+
+    /// The output of the given name.
+    pub fn output(&self, name: &str) -> Option<&OnnxTensor> {
+        self.outputs.iter().find(|output| output.name == name)
+    }
+
+    /// The outputs with their shapes, as written in the errors.
+    pub fn output_names(&self) -> String {
+        self.outputs
+            .iter()
+            .map(|output| format!("'{}' {}", output.name, output.shape_name()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Check that the output of the given name is token-level, i.e. of shape
+    /// `(batch, tokens, dimensions)`, if the model declares its shape.
+    ///
+    /// The error lists the outputs to choose from.
+    pub fn token_output(&self, key: &str) -> Result<&OnnxTensor, String> {
+        match self.output(key) {
+            Some(output) if matches!(&output.shape, Some(shape) if shape.len() != 3) => {
+                Err(format!(
+                    "the output '{key}' of shape {shape} is not token-level; its outputs are \
+                     {outputs}.",
+                    shape = output.shape_name(),
+                    outputs = self.output_names()
+                ))
+            }
+            Some(output) => Ok(output),
+            None => Err(format!(
+                "the model has no output '{key}'; its outputs are {outputs}.",
+                outputs = self.output_names()
+            )),
+        }
+    }
+
+    /// Choose the output for the embeddings of a model of this `kind`.
+    ///
+    /// A given `output_key` must be one of the outputs. Otherwise, the first output of
+    /// [`DENSE_OUTPUT_PRECEDENCE`] or [`SPARSE_OUTPUT_PRECEDENCE`] found is used, or the only
+    /// output if the model has just one.
+    ///
+    /// The error lists the outputs to choose from.
+    pub fn select_output(
+        &self,
+        kind: ModelKind,
+        output_key: Option<&str>,
+    ) -> Result<OutputSelection, String> {
+        let (output, source) = match output_key {
+            Some(key) => (
+                self.output(key).ok_or_else(|| {
+                    format!(
+                        "the model has no output '{key}'; its outputs are {outputs}.",
+                        outputs = self.output_names()
+                    )
+                })?,
+                OutputSource::Configured,
+            ),
+            None => {
+                let precedence: &[&str] = match kind {
+                    ModelKind::Sparse => &SPARSE_OUTPUT_PRECEDENCE,
+                    _ => &DENSE_OUTPUT_PRECEDENCE,
+                };
+
+                (
+                    precedence
+                        .iter()
+                        .find_map(|key| self.output(key))
+                        .or(match self.outputs.as_slice() {
+                            [output] => Some(output),
+                            _ => None,
+                        })
+                        .ok_or_else(|| {
+                            format!(
+                                "no output key was set, and none of {precedence:?} is an output; \
+                                 set one of {outputs}.",
+                                outputs = self.output_names()
+                            )
+                        })?,
+                    OutputSource::Detected,
+                )
+            }
+        };
+
+        Ok(OutputSelection {
+            key: output.name.clone(),
+            source,
+            dimension: match kind {
+                // The last dimension of the logits is the vocabulary, not the embedding.
+                ModelKind::Sparse => None,
+                _ => output.last_dimension(),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use prost::Message;
+
+    /// Encode a graph with the given outputs, and the weights listed as inputs by old exporters.
+    fn encode(outputs: &[(&str, &[i64])]) -> Vec<u8> {
+        let value_info = |name: &str, dims: &[i64]| proto::ValueInfoProto {
+            name: name.to_owned(),
+            r#type: Some(proto::TypeProto {
+                tensor_type: Some(proto::TensorTypeProto {
+                    elem_type: 1,
+                    shape: Some(proto::TensorShapeProto {
+                        dim: dims
+                            .iter()
+                            .map(|&size| match size {
+                                0 => proto::DimensionProto {
+                                    dim_value: None,
+                                    dim_param: Some("batch_size".to_owned()),
+                                },
+                                size => proto::DimensionProto {
+                                    dim_value: Some(size),
+                                    dim_param: None,
+                                },
+                            })
+                            .collect(),
+                    }),
+                }),
+            }),
+        };
+
+        proto::ModelProto {
+            ir_version: 8,
+            producer_name: "pytorch".to_owned(),
+            producer_version: "2.1.0".to_owned(),
+            graph: Some(proto::GraphProto {
+                initializer: vec![proto::TensorProto {
+                    name: "embeddings.weight".to_owned(),
+                }],
+                input: vec![
+                    value_info("input_ids", &[0, 0]),
+                    value_info("embeddings.weight", &[30522, 384]),
+                ],
+                output: outputs
+                    .iter()
+                    .map(|(name, dims)| value_info(name, dims))
+                    .collect(),
+                ..Default::default()
+            }),
+            opset_import: vec![proto::OperatorSetIdProto {
+                domain: String::new(),
+                version: 14,
+            }],
+        }
+        .encode_to_vec()
+    }
+
+    #[test]
+    fn parse_graph() {
+        let graph = OnnxGraph::parse(&encode(&[("last_hidden_state", &[0, 0, 384])]))
+            .expect("Failed to parse the graph.");
+
+        assert_eq!(graph.ir_version, 8);
+        assert_eq!(graph.producer, "pytorch 2.1.0");
+        assert_eq!(graph.opsets[0].version, 14);
+        assert_eq!(
+            graph
+                .inputs
+                .iter()
+                .map(|input| input.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["input_ids"]
+        );
+        assert_eq!(
+            graph.outputs[0].shape_name(),
+            "(batch_size, batch_size, 384)"
+        );
+        assert_eq!(graph.outputs[0].element_type, "float");
+    }
+
+    #[test]
+    fn select_output_by_precedence() {
+        let graph = OnnxGraph::parse(&encode(&[
+            ("last_hidden_state", &[0, 0, 384]),
+            ("sentence_embedding", &[0, 384]),
+        ]))
+        .expect("Failed to parse the graph.");
+
+        assert_eq!(
+            graph.select_output(ModelKind::Dense, None),
+            Ok(OutputSelection {
+                key: "sentence_embedding".to_owned(),
+                source: OutputSource::Detected,
+                dimension: Some(384),
+            })
+        );
+        assert_eq!(
+            graph
+                .select_output(ModelKind::Dense, Some("last_hidden_state"))
+                .map(|selection| selection.source),
+            Ok(OutputSource::Configured)
+        );
+        assert!(graph
+            .select_output(ModelKind::Dense, Some("logits"))
+            .is_err_and(|err| err.contains("'sentence_embedding' (batch_size, 384)")));
+        assert!(graph.select_output(ModelKind::Sparse, None).is_err());
+    }
+
+    #[test]
+    fn check_token_output() {
+        let graph = OnnxGraph::parse(&encode(&[
+            ("last_hidden_state", &[0, 0, 384]),
+            ("sentence_embedding", &[0, 384]),
+        ]))
+        .expect("Failed to parse the graph.");
+
+        assert_eq!(
+            graph
+                .token_output("last_hidden_state")
+                .map(|output| output.last_dimension()),
+            Ok(Some(384))
+        );
+        assert!(graph
+            .token_output("sentence_embedding")
+            .is_err_and(|err| err.contains("is not token-level")));
+        assert!(graph
+            .token_output("token_embeddings")
+            .is_err_and(|err| err.contains("has no output")));
+    }
+
+    #[test]
+    fn select_only_output() {
+        let graph = OnnxGraph::parse(&encode(&[("embeddings", &[0, 768])]))
+            .expect("Failed to parse the graph.");
+
+        assert_eq!(
+            graph
+                .select_output(ModelKind::Dense, None)
+                .map(|selection| (selection.key, selection.dimension)),
+            Ok(("embeddings".to_owned(), Some(768)))
+        );
+        assert!(OnnxGraph::parse(b"not a model").is_err());
+    }
+}
@@ -0,0 +1,26 @@
+//! Per-call options for turning the model output into embeddings.
+
+use super::{InputType, Normalization, Pooling};
+
+/// Overrides of how the model output is turned into embeddings.
+///
+/// The default options reproduce the embeddings of
+/// [`CanTransform::embed_to_array`](super::CanTransform::embed_to_array).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EmbedOptions {
+    /// The pooling to apply instead of the default pooling of the model.
+    pub pooling: Option<Pooling>,
+
+    /// The normalization of the pooled embeddings.
+    pub normalization: Normalization,
+
+    /// Keep only the first `dimensions` of each embedding.
+    ///
+    /// This must be one of the [`CanTransform::matryoshka_dimensions`](super::CanTransform::matryoshka_dimensions)
+    /// of the model.
+    pub dimensions: Option<usize>,
+
+    /// Prepend the prefix the model expects for this type of input, see
+    /// [`CanTransform::prompts`](super::CanTransform::prompts).
+    pub input_type: Option<InputType>,
+}
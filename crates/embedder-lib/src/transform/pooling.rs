@@ -0,0 +1,170 @@
+//! Pooling of token-level embeddings into one embedding per document.
+//!
+//! Unlike [`fastembed::Pooling`](embedder_external::fastembed::Pooling), these operate on the
+//! token-level output of the model, so that the pooling can be chosen per request.
+
+use embedder_err::EmbedderError;
+use embedder_external::serde::Deserialize;
+use embedder_external::{fastembed, ndarray};
+
+/// Pooling methods that can be requested per call.
+///
+/// Deserialized from the [`Pooling::name`] of each method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pooling {
+    /// Arithmetic mean of the token embeddings.
+    Mean,
+    /// The embedding of the first token, typically `[CLS]`.
+    Cls,
+    /// Element-wise maximum of the token embeddings.
+    Max,
+    /// The embedding of the last token, as used by decoder-based models.
+    LastToken,
+    /// Mean of the token embeddings weighted by their positions, as used by SGPT.
+    ///
+    /// Later tokens have attended to more of the input in decoder-based models, so the
+    /// `i`-th token has a weight proportional to `i`.
+    WeightedMean,
+    /// No pooling; the selected output of the model is already pooled.
+    None,
+}
+
+impl Pooling {
+    /// The name of the pooling method.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Mean => "mean",
+            Self::Cls => "cls",
+            Self::Max => "max",
+            Self::LastToken => "last_token",
+            Self::WeightedMean => "weighted_mean",
+            Self::None => "none",
+        }
+    }
+}
+
+/// The methods of `fastembed`, which are applied to the output of the model in the same way.
+impl From<fastembed::Pooling> for Pooling {
+    fn from(pooling: fastembed::Pooling) -> Self {
+        match pooling {
+            fastembed::Pooling::Mean => Self::Mean,
+            fastembed::Pooling::Cls => Self::Cls,
+        }
+    }
+}
+
+/// Pool the token embeddings of shape `(batch, tokens, dimensions)` into `(batch, dimensions)`.
+///
+/// Only the tokens with a non-zero `attention_mask` are considered, so both left and right
+/// padding are supported; positions for [`Pooling::LastToken`] and [`Pooling::WeightedMean`]
+/// are counted from the first unmasked token. Documents without any tokens are pooled into zeros.
+pub fn pool(
+    hidden: ndarray::ArrayView3<f32>,
+    attention_mask: ndarray::ArrayView2<i64>,
+    pooling: Pooling,
+) -> Result<ndarray::Array2<f32>, EmbedderError> {
+    let (batch_size, _, dimensions) = hidden.dim();
+    let mut pooled = ndarray::Array2::<f32>::zeros((batch_size, dimensions));
+
+    for ((mut output, tokens), mask) in pooled
+        .rows_mut()
+        .into_iter()
+        .zip(hidden.outer_iter())
+        .zip(attention_mask.outer_iter())
+    {
+        let indices = mask
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, value)| (*value != 0).then_some(idx))
+            .collect::<Vec<_>>();
+
+        let (Some(first), Some(last)) = (indices.first(), indices.last()) else {
+            continue;
+        };
+
+        match pooling {
+            Pooling::Mean => {
+                indices.iter().for_each(|idx| output += &tokens.row(*idx));
+                output /= indices.len() as f32;
+            }
+            Pooling::Cls => output.assign(&tokens.row(*first)),
+            Pooling::Max => {
+                output.assign(&tokens.row(*first));
+                indices.iter().skip(1).for_each(|idx| {
+                    output.zip_mut_with(&tokens.row(*idx), |acc, value| *acc = acc.max(*value))
+                });
+            }
+            Pooling::LastToken => output.assign(&tokens.row(*last)),
+            Pooling::WeightedMean => {
+                indices.iter().enumerate().for_each(|(position, idx)| {
+                    output.scaled_add((position + 1) as f32, &tokens.row(*idx))
+                });
+                output /= (indices.len() * (indices.len() + 1) / 2) as f32;
+            }
+            Pooling::None => {
+                return Err(EmbedderError::OutputTransformError(
+                    "Token-level embeddings cannot be left unpooled.".to_owned(),
+                ))
+            }
+        }
+    }
+
+    Ok(pooled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> (ndarray::Array3<f32>, ndarray::Array2<i64>) {
+        (
+            ndarray::arr3(&[
+                [[1.0, 2.0], [3.0, -4.0], [9.0, 9.0]],
+                [[9.0, 9.0], [-1.0, 0.0], [5.0, 2.0]],
+            ]),
+            // The first document is right padded, the second is left padded.
+            ndarray::arr2(&[[1, 1, 0], [0, 1, 1]]),
+        )
+    }
+
+    #[test]
+    fn pooling_respects_attention_mask() {
+        let (hidden, mask) = sample();
+
+        for (pooling, expected) in [
+            (Pooling::Mean, ndarray::arr2(&[[2.0, -1.0], [2.0, 1.0]])),
+            (Pooling::Cls, ndarray::arr2(&[[1.0, 2.0], [-1.0, 0.0]])),
+            (Pooling::Max, ndarray::arr2(&[[3.0, 2.0], [5.0, 2.0]])),
+            (
+                Pooling::LastToken,
+                ndarray::arr2(&[[3.0, -4.0], [5.0, 2.0]]),
+            ),
+            (
+                Pooling::WeightedMean,
+                ndarray::arr2(&[[7.0 / 3.0, -2.0], [3.0, 4.0 / 3.0]]),
+            ),
+        ] {
+            let actual = pool(hidden.view(), mask.view(), pooling).expect("Pooling failed.");
+            assert_eq!(actual, expected, "Mismatch for {pooling:?}.");
+        }
+    }
+
+    #[test]
+    fn deserialize_from_names() {
+        use embedder_external::serde_json;
+
+        for pooling in [
+            Pooling::Mean,
+            Pooling::Cls,
+            Pooling::Max,
+            Pooling::LastToken,
+            Pooling::WeightedMean,
+            Pooling::None,
+        ] {
+            let actual: Pooling = serde_json::from_value(pooling.name().into())
+                .expect("Failed to deserialize the pooling method.");
+            assert_eq!(actual, pooling);
+        }
+    }
+}
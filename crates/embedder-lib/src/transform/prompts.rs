@@ -0,0 +1,100 @@
+//! Prompt templates applied to the input texts before tokenization.
+//!
+//! Models such as E5 and BGE are trained with prefixes like `query: ` and `passage: `;
+//! leaving them out does not fail, but silently degrades the retrieval quality.
+
+use std::borrow::Cow;
+
+/// The role of the input texts in retrieval.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputType {
+    /// Search queries.
+    Query,
+    /// Documents or passages to be searched.
+    Document,
+}
+
+impl InputType {
+    /// The name of the input type.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Query => "query",
+            Self::Document => "document",
+        }
+    }
+}
+
+/// The prefixes a model expects for each [`InputType`].
+///
+/// The prefixes are borrowed from the model, which owns them if it was loaded at runtime.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PromptTemplates<'p> {
+    /// The prefix for [`InputType::Query`], e.g. `query: `.
+    pub query: Option<&'p str>,
+
+    /// The prefix for [`InputType::Document`], e.g. `passage: `.
+    pub document: Option<&'p str>,
+}
+
+impl<'p> PromptTemplates<'p> {
+    /// No prefixes for any input type.
+    pub const NONE: Self = Self {
+        query: None,
+        document: None,
+    };
+
+    /// The prefix for the input type, if any.
+    pub fn prompt(&self, input_type: InputType) -> Option<&'p str> {
+        match input_type {
+            InputType::Query => self.query,
+            InputType::Document => self.document,
+        }
+    }
+
+    /// Prepend the prefix for the input type to each of the texts.
+    ///
+    /// The texts are borrowed as they are if there is no prefix to apply.
+    pub fn apply<'t, S: AsRef<str>>(
+        &self,
+        texts: &'t [S],
+        input_type: Option<InputType>,
+    ) -> Vec<Cow<'t, str>> {
+        let prompt = input_type.and_then(|input_type| self.prompt(input_type));
+
+        texts
+            .iter()
+            .map(|text| match prompt {
+                Some(prompt) => Cow::Owned(format!("{prompt}{text}", text = text.as_ref())),
+                None => Cow::Borrowed(text.as_ref()),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_prefixes() {
+        let templates = PromptTemplates {
+            query: Some("query: "),
+            document: Some("passage: "),
+        };
+        let texts = ["What is Rust?"];
+
+        assert_eq!(
+            templates.apply(&texts, Some(InputType::Query)),
+            vec!["query: What is Rust?"]
+        );
+        assert_eq!(
+            templates.apply(&texts, Some(InputType::Document)),
+            vec!["passage: What is Rust?"]
+        );
+        assert_eq!(templates.apply(&texts, None), vec!["What is Rust?"]);
+        assert_eq!(
+            PromptTemplates::NONE.apply(&texts, Some(InputType::Query)),
+            vec!["What is Rust?"]
+        );
+    }
+}
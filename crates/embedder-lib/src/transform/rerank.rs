@@ -0,0 +1,37 @@
+//! Traits for cross-encoder reranking models.
+//!
+
+use super::models::HasEmbeddedSize;
+use super::tokens::{self, Tokenizer};
+use embedder_err::EmbedderError;
+
+pub trait CanRerank: HasEmbeddedSize {
+    /// The name of the model.
+    fn name(&self) -> &str;
+
+    /// The tokenizer used by the model.
+    fn tokenizer(&self) -> &Tokenizer;
+
+    /// The fingerprint of the model files, as verified against their manifest.
+    ///
+    /// See [`crate::integrity::fingerprint`]; `None` if the files were not verified.
+    fn fingerprint(&self) -> Option<String> {
+        None
+    }
+
+    /// Score each of the documents for their relevance to the query.
+    ///
+    /// Each `(query, document)` pair is fed through the cross-encoder, and the relevance logit
+    /// is returned in the order of the documents; higher is more relevant.
+    fn rerank<S: AsRef<str> + Send + Sync>(
+        &self,
+        query: S,
+        documents: Vec<S>,
+        batch_size: Option<usize>,
+    ) -> Result<Vec<f32>, EmbedderError>;
+
+    /// The maximum number of tokens per pair before the input is truncated.
+    fn max_input_length(&self) -> Option<usize> {
+        tokens::max_input_length(self.tokenizer())
+    }
+}
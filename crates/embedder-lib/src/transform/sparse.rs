@@ -0,0 +1,88 @@
+//! Learned sparse embeddings, as produced by SPLADE models.
+//!
+//! SPLADE models are exported with their masked language modelling head, so the output is
+//! the logits over the vocabulary for each token. These are turned into one sparse vector
+//! over the vocabulary per document.
+
+use embedder_external::ndarray;
+
+/// A sparse embedding, keeping only the non-zero dimensions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SparseEmbedding {
+    /// The vocabulary ids of the non-zero dimensions, in ascending order.
+    pub indices: Vec<u32>,
+
+    /// The weights of the dimensions in `indices`.
+    pub values: Vec<f32>,
+}
+
+impl SparseEmbedding {
+    /// The number of non-zero dimensions.
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Whether all the dimensions are zero.
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+}
+
+/// Pool the MLM logits of shape `(batch, tokens, vocabulary)` into sparse embeddings.
+///
+/// Each logit is activated with `log(1 + ReLU(x))`, then the maximum over all the tokens with
+/// a non-zero `attention_mask` is taken for each vocabulary entry.
+pub fn splade_pool(
+    logits: ndarray::ArrayView3<f32>,
+    attention_mask: ndarray::ArrayView2<i64>,
+) -> Vec<SparseEmbedding> {
+    logits
+        .outer_iter()
+        .zip(attention_mask.outer_iter())
+        .map(|(tokens, mask)| {
+            let mut pooled = ndarray::Array1::<f32>::zeros(tokens.ncols());
+
+            tokens
+                .outer_iter()
+                .zip(mask.iter())
+                .filter(|(_, mask)| **mask != 0)
+                .for_each(|(token, _)| {
+                    pooled.zip_mut_with(&token, |acc, logit| *acc = acc.max(logit.max(0.0).ln_1p()))
+                });
+
+            let (indices, values) = pooled
+                .iter()
+                .enumerate()
+                .filter(|(_, value)| **value > 0.0)
+                .map(|(idx, value)| (idx as u32, *value))
+                .unzip();
+
+            SparseEmbedding { indices, values }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splade_pool_respects_attention_mask() {
+        let logits = ndarray::arr3(&[[
+            [-1.0, 0.0, 1.0, 0.0],
+            [0.0, 3.0, -2.0, 0.0],
+            [9.0, 9.0, 9.0, 9.0],
+        ]]);
+        let mask = ndarray::arr2(&[[1, 1, 0]]);
+
+        let embeddings = splade_pool(logits.view(), mask.view());
+
+        assert_eq!(
+            embeddings,
+            vec![SparseEmbedding {
+                indices: vec![1, 2],
+                values: vec![3.0_f32.ln_1p(), 1.0_f32.ln_1p()],
+            }]
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! Tokenization of the input texts, using the same tokenizer as the model.
+//!
+
+use embedder_err::EmbedderError;
+use embedder_external::tokenizers;
+
+/// The tokenizer type used by [`fastembed`](embedder_external::fastembed) models.
+pub type Tokenizer = tokenizers::TokenizerImpl<
+    tokenizers::ModelWrapper,
+    tokenizers::NormalizerWrapper,
+    tokenizers::PreTokenizerWrapper,
+    tokenizers::PostProcessorWrapper,
+    tokenizers::DecoderWrapper,
+>;
+
+/// A single token produced by the tokenizer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    /// The id of the token in the vocabulary.
+    pub id: u32,
+
+    /// The token string as found in the vocabulary.
+    pub token: String,
+
+    /// Whether this token was added by the tokenizer, e.g. `[CLS]` or `[SEP]`.
+    pub special: bool,
+
+    /// Whether this token belongs to the prompt prepended to the text, rather than the text.
+    pub prompt: bool,
+
+    /// The character offsets of the token in the original text.
+    pub offsets: (usize, usize),
+}
+
+/// The tokens of a single document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenizedDocument {
+    pub tokens: Vec<Token>,
+
+    /// Whether the document was truncated to fit the maximum input length of the model.
+    pub truncated: bool,
+}
+
+impl TokenizedDocument {
+    /// Build from a [`tokenizers::Encoding`].
+    pub fn from_encoding(encoding: &tokenizers::Encoding) -> Self {
+        Self {
+            tokens: encoding
+                .get_ids()
+                .iter()
+                .zip(encoding.get_tokens())
+                .zip(encoding.get_special_tokens_mask())
+                .zip(encoding.get_offsets())
+                .map(|(((id, token), special), offsets)| Token {
+                    id: *id,
+                    token: token.clone(),
+                    special: *special != 0,
+                    prompt: false,
+                    offsets: *offsets,
+                })
+                .collect(),
+            truncated: !encoding.get_overflowing().is_empty(),
+        }
+    }
+
+    /// Re-base the offsets onto the text without its prompt of `prompt_length` characters.
+    ///
+    /// The tokens of the prompt are flagged as [`Token::prompt`], with empty offsets at the
+    /// start of the text.
+    pub fn strip_prompt(mut self, prompt_length: usize) -> Self {
+        if prompt_length == 0 {
+            return self;
+        }
+
+        self.tokens.iter_mut().for_each(|token| {
+            let (start, end) = token.offsets;
+            token.prompt = !token.special && end <= prompt_length;
+            token.offsets = (
+                start.saturating_sub(prompt_length),
+                end.saturating_sub(prompt_length),
+            );
+        });
+
+        self
+    }
+
+    /// The number of tokens in the document.
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Whether the document produced no tokens at all.
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+}
+
+/// Tokenize each of the texts individually.
+///
+/// Texts are not encoded as a batch, so that no padding tokens are added.
+pub fn tokenize<S: AsRef<str>>(
+    tokenizer: &Tokenizer,
+    texts: &[S],
+    add_special_tokens: bool,
+) -> Result<Vec<TokenizedDocument>, EmbedderError> {
+    texts
+        .iter()
+        .map(|text| {
+            tokenizer
+                .encode_char_offsets(text.as_ref(), add_special_tokens)
+                .map(|encoding| TokenizedDocument::from_encoding(&encoding))
+                .map_err(|err| EmbedderError::TokenizerError(err.to_string()))
+        })
+        .collect()
+}
+
+/// Decode each of the sequences of token ids back into text.
+pub fn detokenize(
+    tokenizer: &Tokenizer,
+    ids: &[Vec<u32>],
+    skip_special_tokens: bool,
+) -> Result<Vec<String>, EmbedderError> {
+    ids.iter()
+        .map(|ids| {
+            tokenizer
+                .decode(ids, skip_special_tokens)
+                .map_err(|err| EmbedderError::TokenizerError(err.to_string()))
+        })
+        .collect()
+}
+
+/// The maximum number of tokens the tokenizer will produce before truncating.
+pub fn max_input_length(tokenizer: &Tokenizer) -> Option<usize> {
+    tokenizer.get_truncation().map(|params| params.max_length)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn strip_prompt_rebases_offsets() {
+        let token = |id, token: &str, special, offsets| Token {
+            id,
+            token: token.to_owned(),
+            special,
+            prompt: false,
+            offsets,
+        };
+        // "query: Rust" with the prompt "query: " of 7 characters.
+        let document = TokenizedDocument {
+            tokens: vec![
+                token(101, "[CLS]", true, (0, 0)),
+                token(23032, "query", false, (0, 5)),
+                token(1024, ":", false, (5, 6)),
+                token(18399, "rust", false, (7, 11)),
+                token(102, "[SEP]", true, (0, 0)),
+            ],
+            truncated: false,
+        }
+        .strip_prompt(7);
+
+        assert_eq!(
+            document
+                .tokens
+                .iter()
+                .map(|token| (token.prompt, token.offsets))
+                .collect::<Vec<_>>(),
+            vec![
+                (false, (0, 0)),
+                (true, (0, 0)),
+                (true, (0, 0)),
+                (false, (0, 4)),
+                (false, (0, 0)),
+            ]
+        );
+    }
+}
@@ -1,38 +1,165 @@
 //! Traits for embedding models.
 //!
 
+use super::models::HasEmbeddedSize;
+use super::tokens::{self, TokenizedDocument, Tokenizer};
+use super::{
+    pool, splade_pool, EmbedOptions, InputType, ModelKind, Normalization, OutputSelection, Pooling,
+    PromptTemplates, SparseEmbedding,
+};
 use crate::Embedding;
 use embedder_err::EmbedderError;
 use embedder_external::{fastembed, ndarray};
 
-const EPS: f32 = 1e-12;
+/// Select the output of a batch by its name.
+///
+/// Unlike [`fastembed::OutputKey::ByName`], the name does not need to be `'static`, so that the
+/// models loaded at runtime can own their output keys.
+fn select_output<'b>(
+    batch: &'b fastembed::SingleBatchOutput<'_, '_>,
+    key: &str,
+) -> anyhow::Result<ndarray::ArrayViewD<'b, f32>> {
+    batch
+        .session_outputs
+        .get(key)
+        .ok_or_else(|| anyhow::Error::msg(format!("The model has no output '{key}'.")))?
+        .try_extract_tensor::<f32>()
+        .map_err(anyhow::Error::from)
+}
 
-pub trait CanTransform {
+pub trait CanTransform: HasEmbeddedSize {
     /// The name of the model.
     fn name(&self) -> &str;
 
     /// The chosen key for the output embeddings.
-    fn output_key(&self) -> &'static str;
+    fn output_key(&self) -> &str;
+
+    /// How the output was chosen, for the models that inspect their ONNX graph when loaded.
+    ///
+    /// `None` for the models whose output key is fixed.
+    fn output_selection(&self) -> Option<OutputSelection> {
+        None
+    }
+
+    /// The kind of embeddings the model produces.
+    ///
+    /// For [`ModelKind::Sparse`], the [`CanTransform::output_key`] should be the MLM logits
+    /// of shape `(batch, tokens, vocabulary)`.
+    fn kind(&self) -> ModelKind {
+        ModelKind::Dense
+    }
+
+    /// The key for the token-level output embeddings, if the model produces them.
+    ///
+    /// This is typically `token_embeddings` for Sentence Transformers models exported by
+    /// `optimum`, or `last_hidden_state` for plain transformer models, of shape
+    /// `(batch, tokens, dimensions)`.
+    ///
+    /// Models opt in explicitly; without it, the pooling cannot be overridden and no token-level
+    /// embeddings are produced.
+    fn token_output_key(&self) -> Option<&str> {
+        None
+    }
+
+    /// The tokenizer used by the model.
+    fn tokenizer(&self) -> &Tokenizer;
 
     /// The pooling method to use.
     fn pooling(&self) -> Option<fastembed::Pooling>;
 
-    /// The key for the output embeddings.
+    /// The pooling of the token-level output, for methods not covered by [`fastembed::Pooling`].
     ///
-    /// This should only provide one key - test the model to see what keys it produces,
-    /// and choose the one that produce the desired embeddings.
+    /// Decoder-based models typically require [`Pooling::LastToken`] or
+    /// [`Pooling::WeightedMean`]; if set, this takes precedence over [`CanTransform::pooling`],
+    /// which should then be `None`.
+    fn token_pooling(&self) -> Option<Pooling> {
+        None
+    }
+
+    /// The prefixes the model expects for queries and documents.
+    fn prompts(&self) -> PromptTemplates<'_> {
+        PromptTemplates::NONE
+    }
+
+    /// The number of dimensions of the embeddings, if known without running the model.
+    fn dimension(&self) -> Option<usize> {
+        None
+    }
+
+    /// The sizes the embeddings can be truncated to.
     ///
-    /// Instead of trying different keys in succession, the embedding operation
-    /// should fail if the key is not found.
-    fn output_precedence<'e>(&self) -> [fastembed::OutputKey; 1] {
-        [fastembed::OutputKey::ByName(self.output_key())]
+    /// Only models trained with Matryoshka Representation Learning produce embeddings whose
+    /// prefixes are usable on their own; for any other models, this should be empty.
+    fn matryoshka_dimensions(&self) -> Vec<usize> {
+        Vec::new()
     }
 
-    /// Static function to converts the output to a 2D array.
-    fn output_to_2d_array<'r, 's>(
+    /// The fingerprint of the model files, as verified against their manifest.
+    ///
+    /// See [`crate::integrity::fingerprint`]; `None` if the files were not verified.
+    fn fingerprint(&self) -> Option<String> {
+        None
+    }
+
+    /// The pooling methods that can be requested in [`EmbedOptions::pooling`].
+    ///
+    /// [`Pooling::None`] is only supported if the [`CanTransform::output_key`] is already
+    /// pooled; the other methods require a [`CanTransform::token_output_key`].
+    fn supported_pooling(&self) -> Vec<Pooling> {
+        let mut supported = Vec::new();
+
+        if self.pooling().is_none() && self.token_pooling().is_none() {
+            supported.push(Pooling::None);
+        }
+        if self.token_output_key().is_some() {
+            supported.extend([
+                Pooling::Mean,
+                Pooling::Cls,
+                Pooling::Max,
+                Pooling::LastToken,
+                Pooling::WeightedMean,
+            ]);
+        }
+
+        supported
+    }
+
+    /// Check the options against what the model supports, before any embedding is done.
+    fn validate_options(&self, options: &EmbedOptions) -> Result<(), EmbedderError> {
+        if let Some(pooling) = options.pooling {
+            let supported = self.supported_pooling();
+            if !supported.contains(&pooling) {
+                return Err(EmbedderError::UnsupportedPooling {
+                    name: self.name().to_owned(),
+                    pooling: pooling.name(),
+                    supported: supported.iter().map(Pooling::name).collect(),
+                });
+            }
+        }
+
+        if let Some(dimensions) = options.dimensions {
+            let supported = self.matryoshka_dimensions();
+            if !supported.contains(&dimensions) {
+                return Err(EmbedderError::UnsupportedDimensions {
+                    name: self.name().to_owned(),
+                    dimensions,
+                    supported,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Static function to converts the output to a 2D array, without normalization.
+    fn output_to_pooled_array<'r, 's>(
         &self,
         output: fastembed::EmbeddingOutput<'r, 's>,
     ) -> Result<ndarray::Array2<f32>, EmbedderError> {
+        if let Some(pooling) = self.token_pooling() {
+            return self.output_to_token_pooled_array(output, pooling);
+        }
+
         output
             .export_with_transformer(
                 // This needs to be `anyhow::Result`
@@ -40,10 +167,19 @@ pub trait CanTransform {
                     batches
                         .iter()
                         .map(|batch| {
-                            batch.select_and_pool_output(
-                                &self.output_precedence().as_slice(),
-                                self.pooling(),
-                            )
+                            let tensor = select_output(batch, self.output_key())?;
+
+                            match self.pooling() {
+                                Some(pooling) => pool(
+                                    tensor.into_dimensionality::<ndarray::Ix3>()?,
+                                    batch.attention_mask_array.view(),
+                                    pooling.into(),
+                                )
+                                .map_err(anyhow::Error::from),
+                                None => {
+                                    Ok(tensor.into_dimensionality::<ndarray::Ix2>()?.to_owned())
+                                }
+                            }
                         })
                         .reduce(|acc, res| match (acc, res) {
                             (Err(e), _) => return Err(e),
@@ -57,22 +193,151 @@ pub trait CanTransform {
                 },
             )
             .map_err(EmbedderError::FastEmbedError)
-            .map(
-                |mut array: ndarray::ArrayBase<
-                    ndarray::OwnedRepr<f32>,
-                    ndarray::Dim<[usize; 2]>,
-                >| {
-                    // Normalize the embeddings
-                    array.rows_mut().into_iter().for_each(|mut row| {
-                        let norm = row.map(|v| v.powi(2)).sum().sqrt();
-                        for val in row.iter_mut() {
-                            *val /= norm + EPS;
-                        }
-                    });
-
-                    array
-                },
-            )
+    }
+
+    /// Static function to converts the output to a 2D array.
+    fn output_to_2d_array<'r, 's>(
+        &self,
+        output: fastembed::EmbeddingOutput<'r, 's>,
+    ) -> Result<ndarray::Array2<f32>, EmbedderError> {
+        // Normalize the embeddings
+        self.output_to_pooled_array(output)
+            .map(|array| Normalization::L2.apply(array))
+    }
+
+    /// Static function to pool the token-level output into a 2D array, without normalization.
+    fn output_to_token_pooled_array<'r, 's>(
+        &self,
+        output: fastembed::EmbeddingOutput<'r, 's>,
+        pooling: Pooling,
+    ) -> Result<ndarray::Array2<f32>, EmbedderError> {
+        let key = self
+            .token_output_key()
+            .ok_or_else(|| EmbedderError::UnsupportedPooling {
+                name: self.name().to_owned(),
+                pooling: pooling.name(),
+                supported: self.supported_pooling().iter().map(Pooling::name).collect(),
+            })?;
+
+        output
+            .export_with_transformer(|batches| {
+                let pooled = batches
+                    .iter()
+                    .map(|batch| {
+                        let tensor =
+                            select_output(batch, key)?.into_dimensionality::<ndarray::Ix3>()?;
+
+                        pool(tensor, batch.attention_mask_array.view(), pooling)
+                            .map_err(anyhow::Error::from)
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+
+                ndarray::concatenate(
+                    ndarray::Axis(0),
+                    &pooled.iter().map(|array| array.view()).collect::<Vec<_>>(),
+                )
+                .map_err(anyhow::Error::from)
+            })
+            .map_err(EmbedderError::FastEmbedError)
+    }
+
+    /// Static function to converts the output to a 2D array, applying the given options.
+    ///
+    /// The options are expected to have been checked by [`CanTransform::validate_options`].
+    fn output_to_array_with_options<'r, 's>(
+        &self,
+        output: fastembed::EmbeddingOutput<'r, 's>,
+        options: &EmbedOptions,
+    ) -> Result<ndarray::Array2<f32>, EmbedderError> {
+        let array = match options.pooling {
+            None | Some(Pooling::None) => self.output_to_pooled_array(output)?,
+            Some(pooling) => self.output_to_token_pooled_array(output, pooling)?,
+        };
+
+        let array = match options.dimensions {
+            Some(dimensions) if dimensions > array.ncols() => {
+                return Err(EmbedderError::UnsupportedDimensions {
+                    name: self.name().to_owned(),
+                    dimensions,
+                    supported: self.matryoshka_dimensions(),
+                })
+            }
+            Some(dimensions) => array.slice_move(ndarray::s![.., ..dimensions]),
+            None => array,
+        };
+
+        Ok(options.normalization.apply(array))
+    }
+
+    /// Static function to converts the output to one 2D array of token embeddings per document.
+    ///
+    /// Padding tokens are removed using the attention mask, so each array has the shape
+    /// `(tokens, dimensions)` of its own document.
+    fn output_to_token_arrays<'r, 's>(
+        &self,
+        output: fastembed::EmbeddingOutput<'r, 's>,
+    ) -> Result<Vec<ndarray::Array2<f32>>, EmbedderError> {
+        let key = self.token_output_key().ok_or_else(|| {
+            EmbedderError::OutputTransformError(format!(
+                "'{name}' does not produce token-level embeddings.",
+                name = self.name()
+            ))
+        })?;
+
+        output
+            .export_with_transformer(|batches| {
+                batches.iter().try_fold(Vec::new(), |mut acc, batch| {
+                    let tensor =
+                        select_output(batch, key)?.into_dimensionality::<ndarray::Ix3>()?;
+
+                    acc.extend(
+                        tensor
+                            .outer_iter()
+                            .zip(batch.attention_mask_array.outer_iter())
+                            .map(|(hidden, mask)| {
+                                let indices = mask
+                                    .iter()
+                                    .enumerate()
+                                    .filter_map(|(idx, value)| (*value != 0).then_some(idx))
+                                    .collect::<Vec<_>>();
+
+                                hidden.select(ndarray::Axis(0), &indices)
+                            }),
+                    );
+
+                    Ok(acc)
+                })
+            })
+            .map_err(EmbedderError::FastEmbedError)
+    }
+
+    /// Static function to converts the MLM logits of a SPLADE model into sparse embeddings.
+    fn output_to_sparse<'r, 's>(
+        &self,
+        output: fastembed::EmbeddingOutput<'r, 's>,
+    ) -> Result<Vec<SparseEmbedding>, EmbedderError> {
+        if self.kind() != ModelKind::Sparse {
+            return Err(EmbedderError::OutputTransformError(format!(
+                "'{name}' does not produce sparse embeddings.",
+                name = self.name()
+            )));
+        }
+
+        output
+            .export_with_transformer(|batches| {
+                batches.iter().try_fold(Vec::new(), |mut acc, batch| {
+                    let logits = select_output(batch, self.output_key())?
+                        .into_dimensionality::<ndarray::Ix3>()?;
+
+                    acc.extend(splade_pool(
+                        logits.view(),
+                        batch.attention_mask_array.view(),
+                    ));
+
+                    Ok(acc)
+                })
+            })
+            .map_err(EmbedderError::FastEmbedError)
     }
 
     /// Transforms the input texts into embeddings.
@@ -99,6 +364,145 @@ pub trait CanTransform {
         self.output_to_2d_array(output)
     }
 
+    /// Exports the model to a [`ndarray::Array2<f32>`], applying the given options.
+    fn embed_with_options<'e, 'r, 's, S: AsRef<str> + Send + Sync>(
+        &'e self,
+        texts: Vec<S>,
+        batch_size: Option<usize>,
+        options: &EmbedOptions,
+    ) -> Result<ndarray::Array2<f32>, EmbedderError>
+    where
+        'e: 'r,
+        'e: 's,
+    {
+        self.validate_options(options)?;
+
+        let texts = self.prompts().apply(&texts, options.input_type);
+        let output = self.transform(texts, batch_size)?;
+        self.output_to_array_with_options(output, options)
+    }
+
+    /// Exports the model to a [`ndarray::Array2<f32>`], keeping only the first `dimensions`
+    /// of each embedding.
+    ///
+    /// The truncated embeddings are normalized again. `dimensions` must be one of the
+    /// [`CanTransform::matryoshka_dimensions`] of the model.
+    fn embed_to_truncated_array<'e, 'r, 's, S: AsRef<str> + Send + Sync>(
+        &'e self,
+        texts: Vec<S>,
+        batch_size: Option<usize>,
+        dimensions: usize,
+    ) -> Result<ndarray::Array2<f32>, EmbedderError>
+    where
+        'e: 'r,
+        'e: 's,
+    {
+        self.embed_with_options(
+            texts,
+            batch_size,
+            &EmbedOptions {
+                dimensions: Some(dimensions),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Exports the model to a [`ndarray::Array2<f32>`] without normalizing the embeddings.
+    fn embed_to_pooled_array<'e, 'r, 's, S: AsRef<str> + Send + Sync>(
+        &'e self,
+        texts: Vec<S>,
+        batch_size: Option<usize>,
+    ) -> Result<ndarray::Array2<f32>, EmbedderError>
+    where
+        'e: 'r,
+        'e: 's,
+    {
+        let output = self.transform(texts, batch_size)?;
+        self.output_to_pooled_array(output)
+    }
+
+    /// Exports the token-level embeddings of each document.
+    fn embed_to_token_arrays<'e, 'r, 's, S: AsRef<str> + Send + Sync>(
+        &'e self,
+        texts: Vec<S>,
+        batch_size: Option<usize>,
+    ) -> Result<Vec<ndarray::Array2<f32>>, EmbedderError>
+    where
+        'e: 'r,
+        'e: 's,
+    {
+        let output = self.transform(texts, batch_size)?;
+        self.output_to_token_arrays(output)
+    }
+
+    /// Exports the sparse embeddings of each document; only for [`ModelKind::Sparse`] models.
+    fn embed_to_sparse<'e, 'r, 's, S: AsRef<str> + Send + Sync>(
+        &'e self,
+        texts: Vec<S>,
+        batch_size: Option<usize>,
+    ) -> Result<Vec<SparseEmbedding>, EmbedderError>
+    where
+        'e: 'r,
+        'e: 's,
+    {
+        let output = self.transform(texts, batch_size)?;
+        self.output_to_sparse(output)
+    }
+
+    /// Decode the vocabulary ids of a sparse embedding into their tokens.
+    fn decode_terms(&self, indices: &[u32]) -> Vec<String> {
+        indices
+            .iter()
+            .map(|id| self.tokenizer().id_to_token(*id).unwrap_or_default())
+            .collect()
+    }
+
+    /// Tokenizes the input texts with the tokenizer of the model.
+    fn tokenize<S: AsRef<str>>(
+        &self,
+        texts: &[S],
+        add_special_tokens: bool,
+    ) -> Result<Vec<TokenizedDocument>, EmbedderError> {
+        tokens::tokenize(self.tokenizer(), texts, add_special_tokens)
+    }
+
+    /// Tokenize the texts as [`CanTransform::embed_with_options`] would, with the special tokens
+    /// and the prompt of the `input_type`.
+    ///
+    /// The offsets are relative to the texts without the prompt, see
+    /// [`TokenizedDocument::strip_prompt`].
+    fn tokenize_with_options<S: AsRef<str>>(
+        &self,
+        texts: &[S],
+        input_type: Option<InputType>,
+    ) -> Result<Vec<TokenizedDocument>, EmbedderError> {
+        let prompts = self.prompts();
+        let prompt_length = input_type
+            .and_then(|input_type| prompts.prompt(input_type))
+            .map_or(0, |prompt| prompt.chars().count());
+
+        Ok(
+            tokens::tokenize(self.tokenizer(), &prompts.apply(texts, input_type), true)?
+                .into_iter()
+                .map(|document| document.strip_prompt(prompt_length))
+                .collect(),
+        )
+    }
+
+    /// Decode each of the sequences of token ids back into text.
+    fn detokenize(
+        &self,
+        ids: &[Vec<u32>],
+        skip_special_tokens: bool,
+    ) -> Result<Vec<String>, EmbedderError> {
+        tokens::detokenize(self.tokenizer(), ids, skip_special_tokens)
+    }
+
+    /// The maximum number of tokens per document before the input is truncated.
+    fn max_input_length(&self) -> Option<usize> {
+        tokens::max_input_length(self.tokenizer())
+    }
+
     /// Exports the model to a [`Vec<Embedding>`].
     fn embed_to_vec<'e, 'r, 's, S: AsRef<str> + Send + Sync>(
         &'e self,
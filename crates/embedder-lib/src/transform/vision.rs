@@ -0,0 +1,402 @@
+//! Image embeddings from ONNX vision encoders, such as the image tower of CLIP.
+//!
+
+use super::models::HasEmbeddedSize;
+use super::{ModelBytes, Normalization};
+use embedder_err::EmbedderError;
+use embedder_external::{
+    image::{self, imageops::FilterType, DynamicImage, RgbImage},
+    ndarray, ort, serde_json,
+};
+
+/// The mean of each RGB channel used by CLIP.
+const CLIP_IMAGE_MEAN: [f32; 3] = [0.48145466, 0.4578275, 0.40821073];
+/// The standard deviation of each RGB channel used by CLIP.
+const CLIP_IMAGE_STD: [f32; 3] = [0.26862954, 0.2613026, 0.2757771];
+
+/// How the images are resized before cropping.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resize {
+    /// Resize the shortest edge to this size, keeping the aspect ratio.
+    ShortestEdge(u32),
+    /// Resize to exactly this size, ignoring the aspect ratio.
+    Exact { height: u32, width: u32 },
+}
+
+/// The preprocessing of the images, as described by the `preprocessor_config.json` of the model.
+#[derive(Debug, Clone)]
+pub struct ImagePreprocessor {
+    pub resize: Option<Resize>,
+    pub filter: FilterType,
+    /// The `(height, width)` of the center crop.
+    pub crop: Option<(u32, u32)>,
+    pub rescale_factor: Option<f32>,
+    /// The `(mean, std)` of each RGB channel.
+    pub normalize: Option<([f32; 3], [f32; 3])>,
+}
+
+impl Default for ImagePreprocessor {
+    /// The preprocessing of the CLIP ViT models, at 224 pixels.
+    fn default() -> Self {
+        Self {
+            resize: Some(Resize::ShortestEdge(224)),
+            filter: FilterType::CatmullRom,
+            crop: Some((224, 224)),
+            rescale_factor: Some(1.0 / 255.0),
+            normalize: Some((CLIP_IMAGE_MEAN, CLIP_IMAGE_STD)),
+        }
+    }
+}
+
+/// Read a size from either an integer or a `{"height": ..., "width": ...}` object.
+fn size_from_value(value: &serde_json::Value) -> Option<(u32, u32)> {
+    match value {
+        serde_json::Value::Number(size) => size.as_u64().map(|size| (size as u32, size as u32)),
+        serde_json::Value::Object(size) => Some((
+            size.get("height")?.as_u64()? as u32,
+            size.get("width")?.as_u64()? as u32,
+        )),
+        _ => None,
+    }
+}
+
+/// Read a triplet of floats, one for each RGB channel.
+fn channels_from_value(value: Option<&serde_json::Value>) -> Option<[f32; 3]> {
+    let channels = value?.as_array()?;
+    match channels.as_slice() {
+        [red, green, blue] => Some([
+            red.as_f64()? as f32,
+            green.as_f64()? as f32,
+            blue.as_f64()? as f32,
+        ]),
+        _ => None,
+    }
+}
+
+impl ImagePreprocessor {
+    /// Parse the `preprocessor_config.json` of a Hugging Face image processor.
+    ///
+    /// Both the legacy format, where `size` and `crop_size` are integers, and the current format,
+    /// where `size` is `{"shortest_edge": ...}` or `{"height": ..., "width": ...}`, are supported.
+    /// Any missing mean or standard deviation defaults to that of CLIP.
+    pub fn from_config(config: &[u8]) -> Result<Self, String> {
+        let config: serde_json::Value =
+            serde_json::from_slice(config).map_err(|err| err.to_string())?;
+        let flag = |key: &str| config.get(key).and_then(|value| value.as_bool());
+
+        let resize = match (flag("do_resize"), config.get("size")) {
+            (Some(false), _) | (_, None) => None,
+            (_, Some(serde_json::Value::Number(size))) => {
+                size.as_u64().map(|size| Resize::ShortestEdge(size as u32))
+            }
+            (_, Some(size)) => match size.get("shortest_edge") {
+                Some(edge) => edge.as_u64().map(|edge| Resize::ShortestEdge(edge as u32)),
+                None => {
+                    size_from_value(size).map(|(height, width)| Resize::Exact { height, width })
+                }
+            },
+        };
+        if config.get("size").is_some() && resize.is_none() && flag("do_resize") != Some(false) {
+            return Err(format!("unsupported size {size}", size = config["size"]));
+        }
+
+        let filter = match config.get("resample").and_then(|value| value.as_u64()) {
+            Some(0) => FilterType::Nearest,
+            Some(1) => FilterType::Lanczos3,
+            Some(2) => FilterType::Triangle,
+            Some(3) | None => FilterType::CatmullRom,
+            Some(resample) => return Err(format!("unsupported resample filter {resample}")),
+        };
+
+        let crop = match flag("do_center_crop") {
+            Some(false) => None,
+            _ => config.get("crop_size").and_then(size_from_value),
+        };
+
+        let rescale_factor = match flag("do_rescale") {
+            Some(false) => None,
+            _ => Some(
+                config
+                    .get("rescale_factor")
+                    .and_then(|value| value.as_f64())
+                    .map(|factor| factor as f32)
+                    .unwrap_or(1.0 / 255.0),
+            ),
+        };
+
+        let normalize = match flag("do_normalize") {
+            Some(false) => None,
+            _ => Some((
+                channels_from_value(config.get("image_mean")).unwrap_or(CLIP_IMAGE_MEAN),
+                channels_from_value(config.get("image_std")).unwrap_or(CLIP_IMAGE_STD),
+            )),
+        };
+
+        Ok(Self {
+            resize,
+            filter,
+            crop,
+            rescale_factor,
+            normalize,
+        })
+    }
+
+    /// Resize and crop the image, returning the RGB pixels.
+    fn resize_and_crop(&self, image: &DynamicImage) -> RgbImage {
+        let image = image.to_rgb8();
+
+        let image = match self.resize {
+            Some(Resize::ShortestEdge(edge)) => {
+                let (width, height) = image.dimensions();
+                let scale = edge as f32 / width.min(height) as f32;
+                image::imageops::resize(
+                    &image,
+                    ((width as f32 * scale).round() as u32).max(1),
+                    ((height as f32 * scale).round() as u32).max(1),
+                    self.filter,
+                )
+            }
+            Some(Resize::Exact { height, width }) => {
+                image::imageops::resize(&image, width, height, self.filter)
+            }
+            None => image,
+        };
+
+        match self.crop {
+            Some((height, width)) => {
+                let x = image.width().saturating_sub(width) / 2;
+                let y = image.height().saturating_sub(height) / 2;
+                let cropped = image::imageops::crop_imm(&image, x, y, width, height).to_image();
+
+                // Images smaller than the crop are stretched rather than padded.
+                if cropped.dimensions() != (width, height) {
+                    image::imageops::resize(&cropped, width, height, self.filter)
+                } else {
+                    cropped
+                }
+            }
+            None => image,
+        }
+    }
+
+    /// Preprocess an image into the `(channels, height, width)` pixel values of the model.
+    pub fn preprocess(&self, image: &DynamicImage) -> ndarray::Array3<f32> {
+        let image = self.resize_and_crop(image);
+        let rescale_factor = self.rescale_factor.unwrap_or(1.0);
+
+        ndarray::Array3::from_shape_fn(
+            (3, image.height() as usize, image.width() as usize),
+            |(channel, y, x)| {
+                let value = image.get_pixel(x as u32, y as u32)[channel] as f32 * rescale_factor;
+
+                match self.normalize {
+                    Some((mean, std)) => (value - mean[channel]) / std[channel],
+                    None => value,
+                }
+            },
+        )
+    }
+
+    /// Preprocess a batch of images into the `(batch, channels, height, width)` pixel values.
+    ///
+    /// Without a crop, all the images must resize to the same dimensions.
+    pub fn preprocess_batch(
+        &self,
+        images: &[DynamicImage],
+    ) -> Result<ndarray::Array4<f32>, EmbedderError> {
+        let pixel_values = images
+            .iter()
+            .map(|image| self.preprocess(image))
+            .collect::<Vec<_>>();
+
+        ndarray::stack(
+            ndarray::Axis(0),
+            &pixel_values
+                .iter()
+                .map(|array| array.view())
+                .collect::<Vec<_>>(),
+        )
+        .map_err(|err| EmbedderError::OutputTransformError(err.to_string()))
+    }
+}
+
+/// Decode the bytes of an encoded image, such as a JPEG, PNG or WebP file.
+pub fn decode_image(bytes: &[u8]) -> Result<DynamicImage, String> {
+    image::load_from_memory(bytes).map_err(|err| err.to_string())
+}
+
+/// An ONNX Runtime session, which may borrow the bytes of the model it was created from.
+pub enum ModelSession {
+    /// Created from a copy of the model.
+    Owned(ort::Session),
+    /// Created from the model embedded in the binary, which ONNX Runtime may use in place.
+    Borrowed(ort::InMemorySession<'static>),
+}
+
+impl std::ops::Deref for ModelSession {
+    type Target = ort::Session;
+
+    fn deref(&self) -> &ort::Session {
+        match self {
+            Self::Owned(session) => session,
+            Self::Borrowed(session) => session,
+        }
+    }
+}
+
+/// Create an ONNX Runtime session from the bytes of the model.
+///
+/// The bytes embedded in the binary are handed to ONNX Runtime directly, which then uses them
+/// in place for models in the ORT format instead of copying them; any other bytes are dropped
+/// once the session is created.
+pub fn load_session(onnx_file: ModelBytes) -> Result<ModelSession, ort::Error> {
+    let threads = std::thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(1);
+
+    let builder = ort::Session::builder()?
+        .with_optimization_level(ort::GraphOptimizationLevel::Level3)?
+        .with_intra_threads(threads)?;
+
+    match onnx_file {
+        ModelBytes::Static(bytes) => builder
+            .commit_from_memory_directly(bytes)
+            .map(ModelSession::Borrowed),
+        bytes => builder.commit_from_memory(&bytes).map(ModelSession::Owned),
+    }
+}
+
+pub trait CanEmbedImage: HasEmbeddedSize {
+    /// The name of the model.
+    fn name(&self) -> &str;
+
+    /// The key of the pixel values input.
+    fn input_key(&self) -> &'static str {
+        "pixel_values"
+    }
+
+    /// The key of the pooled image embeddings output, e.g. `image_embeds` for CLIP.
+    fn output_key(&self) -> &'static str;
+
+    /// The name of the text model sharing the vector space with this model, if any.
+    ///
+    /// The text model is a [`CanTransform`](super::CanTransform) model of its own; embeddings
+    /// of both are comparable once normalized.
+    fn text_model(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// The preprocessing of the images.
+    fn preprocessor(&self) -> &ImagePreprocessor;
+
+    /// The ONNX Runtime session of the vision encoder.
+    fn session(&self) -> &ort::Session;
+
+    /// The fingerprint of the model files, as verified against their manifest.
+    ///
+    /// See [`crate::integrity::fingerprint`]; `None` if the files were not verified.
+    fn fingerprint(&self) -> Option<String> {
+        None
+    }
+
+    /// Embed the decoded images, without normalization.
+    fn embed_decoded_images(
+        &self,
+        images: &[DynamicImage],
+        batch_size: Option<usize>,
+    ) -> Result<ndarray::Array2<f32>, EmbedderError> {
+        if images.is_empty() {
+            return Err(EmbedderError::EmptyInputError);
+        }
+
+        let batches = images
+            .chunks(batch_size.unwrap_or(images.len()).max(1))
+            .map(|batch| {
+                let pixel_values = self.preprocessor().preprocess_batch(batch)?;
+
+                let inputs = ort::inputs![
+                    self.input_key() => ort::Value::from_array(pixel_values)
+                        .map_err(|err| EmbedderError::FastEmbedError(err.into()))?,
+                ]
+                .map_err(|err| EmbedderError::FastEmbedError(err.into()))?;
+                let outputs = self
+                    .session()
+                    .run(inputs)
+                    .map_err(|err| EmbedderError::FastEmbedError(err.into()))?;
+
+                outputs
+                    .get(self.output_key())
+                    .ok_or_else(|| {
+                        EmbedderError::OutputTransformError(format!(
+                            "Output '{key}' not found.",
+                            key = self.output_key()
+                        ))
+                    })?
+                    .try_extract_tensor::<f32>()
+                    .map_err(|err| EmbedderError::FastEmbedError(err.into()))?
+                    .into_dimensionality::<ndarray::Ix2>()
+                    .map(|array| array.to_owned())
+                    .map_err(|err| EmbedderError::OutputTransformError(err.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        ndarray::concatenate(
+            ndarray::Axis(0),
+            &batches.iter().map(|array| array.view()).collect::<Vec<_>>(),
+        )
+        .map_err(|err| EmbedderError::OutputTransformError(err.to_string()))
+    }
+
+    /// Decode and embed the encoded images, normalizing the embeddings as requested.
+    fn embed_images<B: AsRef<[u8]>>(
+        &self,
+        images: &[B],
+        batch_size: Option<usize>,
+        normalization: Normalization,
+    ) -> Result<ndarray::Array2<f32>, EmbedderError> {
+        let images = images
+            .iter()
+            .enumerate()
+            .map(|(index, bytes)| {
+                decode_image(bytes.as_ref())
+                    .map_err(|error| EmbedderError::ImageDecodeError { index, error })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.embed_decoded_images(&images, batch_size)
+            .map(|array| normalization.apply(array))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn preprocess_resizes_and_crops() {
+        let preprocessor = ImagePreprocessor::from_config(
+            br#"{
+                "crop_size": {"height": 224, "width": 224},
+                "do_center_crop": true,
+                "do_normalize": true,
+                "do_resize": true,
+                "image_mean": [0.5, 0.5, 0.5],
+                "image_std": [0.5, 0.5, 0.5],
+                "resample": 3,
+                "size": {"shortest_edge": 224}
+            }"#,
+        )
+        .expect("Failed to parse the config.");
+
+        assert_eq!(preprocessor.resize, Some(Resize::ShortestEdge(224)));
+        assert_eq!(preprocessor.crop, Some((224, 224)));
+
+        let image =
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(640, 320, image::Rgb([255, 0, 128])));
+        let pixel_values = preprocessor.preprocess(&image);
+
+        assert_eq!(pixel_values.shape(), &[3, 224, 224]);
+        assert!((pixel_values[[0, 100, 100]] - 1.0).abs() <= 1e-4);
+        assert!((pixel_values[[1, 100, 100]] + 1.0).abs() <= 1e-4);
+    }
+}
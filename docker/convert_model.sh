@@ -3,7 +3,7 @@ if [ -d ./${MODEL} ]; then
     echo "Removing existing model directory."
     rm -rf ./${MODEL}
 fi
-optimum-cli export onnx --model ${MODEL} /tmp/output
+optimum-cli export onnx --model ${MODEL} ${TASK:+--task ${TASK}} /tmp/output
 if [ $? -ne 0 ]; then
     echo "Failed to convert model to ONNX."
     exit 1
@@ -12,4 +12,6 @@ echo "Creating model directory."
 mkdir -p ./${MODEL}
 echo "Moving ONNX model to model directory."
 mv /tmp/output/** ./${MODEL}
+echo "Recording checksums of the model files."
+(cd ./${MODEL} && sha256sum * > SHA256SUMS)
 echo "Completed model conversion."
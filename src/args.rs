@@ -1,6 +1,8 @@
-use embedder_external::clap::{self, Parser};
+use embedder_external::clap::{self, Args, Parser, Subcommand, ValueEnum};
+use embedder_external::fastembed;
 
 use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
 
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
@@ -13,6 +15,92 @@ pub struct CliArgs {
     /// The port to listen on. Defaults to 3000.
     #[arg(short, long, default_value_t = 3000)]
     port: u16,
+
+    /// The port for the gRPC server to listen on. Defaults to 50051.
+    #[cfg(feature = "grpc")]
+    #[arg(long, default_value_t = 50051)]
+    grpc_port: u16,
+
+    /// The number of documents whose token-level embeddings are cached for `/maxsim`.
+    /// Defaults to 4096.
+    #[arg(long, default_value_t = 4096)]
+    token_cache_size: usize,
+
+    /// Load all the models at startup rather than on their first request, reporting the memory
+    /// taken by each with the `status` feature.
+    #[arg(long)]
+    preload: bool,
+
+    /// A folder of models to load at startup, in addition to the ones embedded in the binary.
+    /// Each model of `--models-manifest` found in its `<name>` subfolder, along with its
+    /// `SHA256SUMS`, is served as if it was embedded.
+    #[arg(long)]
+    model_dir: Option<PathBuf>,
+
+    /// The manifest of the models to look for in `--model-dir`. Defaults to the `models.toml`
+    /// this binary was built with.
+    #[arg(long, requires = "model_dir")]
+    models_manifest: Option<PathBuf>,
+
+    /// Run a command instead of the server.
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// The commands run instead of the server.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Describe the ONNX model, the tokenizer and a sample embedding of a model folder, e.g.
+    /// before adding it to `models.toml`.
+    InspectModel(InspectModelArgs),
+}
+
+/// The arguments of `inspect-model`.
+#[derive(Args, Debug)]
+pub struct InspectModelArgs {
+    /// The folder containing the model files.
+    path: PathBuf,
+
+    /// The ONNX model file within the folder.
+    #[arg(long, default_value = "model.onnx")]
+    model_file: String,
+
+    /// The output to embed the sample with. Defaults to the output detected from the graph.
+    #[arg(long)]
+    output_key: Option<String>,
+
+    /// The pooling of the sample embedding.
+    #[arg(long, value_enum, default_value_t = SamplePooling::None)]
+    pooling: SamplePooling,
+
+    /// The document to embed as the sample.
+    #[arg(long, default_value = "Hello, world!")]
+    sample: String,
+
+    /// Print the description as JSON, for scripting.
+    #[arg(long)]
+    json: bool,
+}
+
+/// The pooling of the sample embedding of `inspect-model`, as accepted by `models.toml`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SamplePooling {
+    /// Use the output as it is, e.g. `sentence_embedding`.
+    None,
+    /// The mean of the token embeddings.
+    Mean,
+    /// The embedding of the first token.
+    Cls,
+}
+
+impl From<SamplePooling> for Option<fastembed::Pooling> {
+    fn from(pooling: SamplePooling) -> Self {
+        match pooling {
+            SamplePooling::None => None,
+            SamplePooling::Mean => Some(fastembed::Pooling::Mean),
+            SamplePooling::Cls => Some(fastembed::Pooling::Cls),
+        }
+    }
 }
 
 impl CliArgs {
@@ -20,4 +108,67 @@ impl CliArgs {
     pub fn socket_addr(&self) -> std::net::SocketAddr {
         std::net::SocketAddr::new(self.host.into(), self.port)
     }
+
+    /// Get the socket address of the gRPC server from the host and gRPC port.
+    #[cfg(feature = "grpc")]
+    pub fn grpc_socket_addr(&self) -> std::net::SocketAddr {
+        std::net::SocketAddr::new(self.host.into(), self.grpc_port)
+    }
+
+    /// Get the number of documents to keep in the token-level embeddings cache.
+    pub fn token_cache_size(&self) -> usize {
+        self.token_cache_size
+    }
+
+    /// Whether to load all the models at startup.
+    pub fn preload(&self) -> bool {
+        self.preload
+    }
+
+    /// The folder of the models to load at startup, if any.
+    pub fn model_dir(&self) -> Option<&Path> {
+        self.model_dir.as_deref()
+    }
+
+    /// The manifest of the models to look for in the model folder, if not the default.
+    pub fn models_manifest(&self) -> Option<&Path> {
+        self.models_manifest.as_deref()
+    }
+
+    /// The command to run instead of the server, if any.
+    pub fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
+}
+
+impl InspectModelArgs {
+    /// The folder containing the model files.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The ONNX model file within the folder.
+    pub fn model_file(&self) -> &str {
+        &self.model_file
+    }
+
+    /// The output to embed the sample with, if not detected.
+    pub fn output_key(&self) -> Option<&str> {
+        self.output_key.as_deref()
+    }
+
+    /// The pooling of the sample embedding.
+    pub fn pooling(&self) -> Option<fastembed::Pooling> {
+        self.pooling.into()
+    }
+
+    /// The document to embed as the sample.
+    pub fn sample(&self) -> &str {
+        &self.sample
+    }
+
+    /// Whether to print the description as JSON.
+    pub fn json(&self) -> bool {
+        self.json
+    }
 }
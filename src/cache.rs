@@ -0,0 +1,84 @@
+//! Cache of the token-level embeddings of documents, for late-interaction scoring.
+//!
+//! This is implemented globally as a singleton, shared by all the requests; the oldest
+//! entries are evicted first once the capacity is reached.
+
+use embedder_external::ndarray;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// The global cache of token-level embeddings.
+static TOKEN_CACHE: OnceLock<TokenCache> = OnceLock::new();
+
+/// The default number of documents to keep, if [`TokenCache::init`] was not called.
+const DEFAULT_CAPACITY: usize = 4096;
+
+/// The key of a cached document: the name of the model and the document itself.
+type CacheKey = (&'static str, String);
+
+#[derive(Debug, Default)]
+struct CacheEntries {
+    arrays: HashMap<CacheKey, Arc<ndarray::Array2<f32>>>,
+    order: VecDeque<CacheKey>,
+}
+
+/// A bounded cache of the normalized token-level embeddings of documents.
+#[derive(Debug)]
+pub struct TokenCache {
+    capacity: usize,
+    entries: Mutex<CacheEntries>,
+}
+
+impl TokenCache {
+    /// Initialize the cache with the given capacity, without returning it.
+    pub fn init(capacity: usize) {
+        TOKEN_CACHE.get_or_init(|| Self::new(capacity));
+    }
+
+    /// Get the global instance of the cache.
+    pub fn get() -> &'static Self {
+        TOKEN_CACHE.get_or_init(|| Self::new(DEFAULT_CAPACITY))
+    }
+
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(CacheEntries::default()),
+        }
+    }
+
+    /// Look up the token-level embeddings of a document.
+    pub fn lookup(&self, model: &'static str, document: &str) -> Option<Arc<ndarray::Array2<f32>>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .arrays
+            .get(&(model, document.to_owned()))
+            .cloned()
+    }
+
+    /// Store the token-level embeddings of a document, evicting the oldest entries if full.
+    pub fn insert(&self, model: &'static str, document: String, array: Arc<ndarray::Array2<f32>>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        // A poisoned lock only means another request panicked; the cache is still usable.
+        let mut entries = self
+            .entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let key = (model, document);
+
+        if entries.arrays.insert(key.clone(), array).is_none() {
+            entries.order.push_back(key);
+        }
+
+        while entries.order.len() > self.capacity {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.arrays.remove(&oldest);
+            }
+        }
+    }
+}
@@ -24,3 +24,14 @@ where
 pub fn calculate_default_batch_size(_count: usize) -> usize {
     16
 }
+
+/// Run a blocking closure on the blocking thread pool, flattening the join error.
+pub async fn spawn_blocking<F, T>(func: F) -> Result<T, EmbedderAPIError>
+where
+    F: FnOnce() -> Result<T, EmbedderAPIError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(func)
+        .await
+        .map_err(|err| EmbedderAPIError::ConcurrencyError(err.to_string()))?
+}
@@ -0,0 +1,173 @@
+//! Encodings of the embeddings, for clients that do not want full precision JSON floats.
+
+use embedder_external::serde::{Deserialize, Serialize};
+use embedder_external::{half::f16, ndarray, serde_json};
+use embedder_lib::quantize;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// Element types that can be packed into a [`Base64Array`].
+pub trait LittleEndianBytes: Copy {
+    /// The `numpy` compatible data type of the element.
+    const DTYPE: &'static str;
+
+    /// Append the little-endian bytes of the element.
+    fn extend_le_bytes(self, bytes: &mut Vec<u8>);
+}
+
+macro_rules! impl_little_endian_bytes {
+    ($($ty:ty => $dtype:literal),*$(,)?) => {
+        $(
+            impl LittleEndianBytes for $ty {
+                const DTYPE: &'static str = $dtype;
+
+                fn extend_le_bytes(self, bytes: &mut Vec<u8>) {
+                    bytes.extend(self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_little_endian_bytes!(
+    f32 => "<f4",
+    f16 => "<f2",
+    i8 => "|i1",
+    u8 => "|u1",
+);
+
+/// An array serialized as base64 of its little-endian bytes in row-major order.
+///
+/// This can be decoded in Python with a single call:
+///
+/// ```python
+/// np.frombuffer(base64.b64decode(data), dtype=dtype).reshape(shape)
+/// ```
+#[derive(Debug, Clone, Serialize)]
+pub struct Base64Array {
+    /// The `numpy` compatible data type of each element, e.g. `<f4`.
+    dtype: &'static str,
+    shape: Vec<usize>,
+    data: String,
+}
+
+impl Base64Array {
+    /// Create a new instance from the raw bytes of the array.
+    pub fn new(dtype: &'static str, shape: Vec<usize>, bytes: &[u8]) -> Self {
+        Self {
+            dtype,
+            shape,
+            data: STANDARD.encode(bytes),
+        }
+    }
+}
+
+impl<A: LittleEndianBytes, D: ndarray::Dimension> From<&ndarray::Array<A, D>> for Base64Array {
+    fn from(array: &ndarray::Array<A, D>) -> Self {
+        let mut bytes = Vec::with_capacity(array.len() * std::mem::size_of::<A>());
+        array
+            .iter()
+            .for_each(|value| value.extend_le_bytes(&mut bytes));
+
+        Self::new(A::DTYPE, array.shape().to_vec(), &bytes)
+    }
+}
+
+/// The precision of the returned embeddings.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    #[default]
+    Float32,
+    Float16,
+    Int8,
+    Binary,
+}
+
+/// The parameters required to interpret the quantized embeddings.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged, rename_all_fields = "camelCase")]
+pub enum QuantizationParameters {
+    /// Each row can be dequantized with `scale * (value - zero_point)`.
+    Int8 {
+        scales: Vec<f32>,
+        zero_points: Vec<i32>,
+    },
+    /// Signs of the original dimensions, packed into bytes with `numpy.packbits`.
+    Binary { dimensions: usize },
+}
+
+/// The embeddings at the requested [`Precision`].
+#[derive(Debug, Clone)]
+pub enum Embeddings {
+    Float32(ndarray::Array2<f32>),
+    Float16(ndarray::Array2<f16>),
+    Int8(ndarray::Array2<i8>),
+    Binary(ndarray::Array2<u8>),
+}
+
+/// Apply the same expression to the array of any variant of [`Embeddings`].
+macro_rules! map_embeddings {
+    ($embeddings:expr, |$array:ident| $body:expr) => {
+        match $embeddings {
+            Embeddings::Float32($array) => $body,
+            Embeddings::Float16($array) => $body,
+            Embeddings::Int8($array) => $body,
+            Embeddings::Binary($array) => $body,
+        }
+    };
+}
+
+impl Embeddings {
+    /// Convert the normalized embeddings to the requested precision.
+    ///
+    /// `int8_range` is the calibrated `(min, max)` range shared by all the rows; if not
+    /// given, each row is quantized using its own range.
+    pub fn new(
+        array: ndarray::Array2<f32>,
+        precision: Precision,
+        int8_range: Option<(f32, f32)>,
+    ) -> (Self, Option<QuantizationParameters>) {
+        match precision {
+            Precision::Float32 => (Self::Float32(array), None),
+            Precision::Float16 => (Self::Float16(quantize::to_float16(&array)), None),
+            Precision::Int8 => {
+                let quantized = quantize::to_int8(&array, int8_range);
+                (
+                    Self::Int8(quantized.values),
+                    Some(QuantizationParameters::Int8 {
+                        scales: quantized.scales,
+                        zero_points: quantized.zero_points,
+                    }),
+                )
+            }
+            Precision::Binary => (
+                Self::Binary(quantize::to_binary(&array)),
+                Some(QuantizationParameters::Binary {
+                    dimensions: array.ncols(),
+                }),
+            ),
+        }
+    }
+
+    /// Serialize as a list of embeddings.
+    pub fn to_rows_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+        map_embeddings!(self, |array| serde_json::to_value(
+            array
+                .rows()
+                .into_iter()
+                .map(|row| row.to_vec())
+                .collect::<Vec<_>>()
+        ))
+    }
+
+    /// Serialize using the default `ndarray` serialization.
+    pub fn to_array_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+        map_embeddings!(self, |array| serde_json::to_value(array))
+    }
+
+    /// Serialize as a [`Base64Array`].
+    pub fn to_base64_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::to_value(map_embeddings!(self, |array| Base64Array::from(array)))
+    }
+}
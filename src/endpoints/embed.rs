@@ -1,24 +1,31 @@
 //! The main `embed` endpoint, converting `documents` into `embeddings`.
 
-use embedder_err::EmbedderAPIError;
+use embedder_err::{EmbedderAPIError, EmbedderError};
 use embedder_external::axum::{
     self,
     extract::{Json, Query},
 };
-use embedder_external::serde::{Deserialize, Serialize};
+use embedder_external::serde::{self, Deserialize, Deserializer, Serialize, Serializer};
 use embedder_external::{fastembed, ndarray, serde_json};
-use embedder_lib::{transform::CanTransform, Embedding};
-use std::sync::Arc;
+use embedder_lib::quantize;
+use embedder_lib::transform::{
+    self, models::EmbeddedSize, DynEmbedder, EmbedOptions, ModelKind, Normalization,
+    OutputSelection, Pooling, PromptTemplates, SparseEmbedding, Token, TokenizedDocument,
+};
 use tokio::time::Instant;
 
-use crate::common::{calculate_default_batch_size, ToJsonResponse};
+use super::{runtime_models, RuntimeModel};
+use crate::common::{calculate_default_batch_size, spawn_blocking, ToJsonResponse};
+use crate::encoding::{Base64Array, Embeddings, Precision, QuantizationParameters};
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Copy, Deserialize)]
 pub enum OutputType {
     #[serde(rename = "json")]
     Json,
     #[serde(rename = "array")]
     Array,
+    #[serde(rename = "base64")]
+    Base64,
     #[serde(rename = "pickle")]
     Pickle,
 }
@@ -29,50 +36,166 @@ impl Default for OutputType {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The granularity of the returned embeddings.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    /// One pooled embedding per document.
+    #[default]
+    Document,
+    /// One embedding per token of each document, e.g. for late-interaction retrieval.
+    Token,
+}
+
+/// The role of the `documents`, selecting the prefix the model expects.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputType {
+    Query,
+    Document,
+}
+
+impl From<InputType> for transform::InputType {
+    fn from(value: InputType) -> Self {
+        match value {
+            InputType::Query => Self::Query,
+            InputType::Document => Self::Document,
+        }
+    }
+}
+
+embedder_lib::embedded_models!(text, declare_models!(EmbeddedModel));
+
+/// A text model, either embedded in the binary or loaded at runtime from `--model-dir`.
+///
+/// This is (de)serialized as the name of the model.
+#[derive(Debug, Clone)]
 pub enum EmbeddingModel {
-    #[serde(rename = "sentence-transformers/all-MiniLM-L6-v2")]
-    #[cfg(feature = "sentence_transformers_all_minilm_l6_v2")]
-    SentenceTransformersAllMiniLML6V2,
-
-    #[serde(rename = "sentence-transformers/all-mpnet-base-v2")]
-    #[cfg(feature = "sentence_transformers_all_mpnet_base_v2")]
-    SentenceTransformerAllMpnetBaseV2,
-}
-
-macro_rules! pass_through_method {
-    ($method:ident() -> $output:ty) => {
-        pub fn $method(
-            &self,
-            documents: Vec<String>,
-            batch_size: Option<usize>,
-        ) -> Result<$output, EmbedderAPIError> {
-            match self {
-                #[cfg(feature = "sentence_transformers_all_minilm_l6_v2")]
-                Self::SentenceTransformersAllMiniLML6V2 => {
-                    embedder_lib::transform::models::all_minilm_l6_v2::Model::new()
-                        .and_then(|model| model.$method(documents, batch_size))
-                }
-
-                #[cfg(feature = "sentence_transformers_all_mpnet_base_v2")]
-                Self::SentenceTransformerAllMpnetBaseV2 => {
-                    embedder_lib::transform::models::all_mpnet_base_v2::Model::new()
-                        .and_then(|model| model.$method(documents, batch_size))
-                }
-            }
-            .map_err(EmbedderAPIError::EmbedderError)
+    Embedded(EmbeddedModel),
+    Runtime(&'static RuntimeModel),
+    /// The deterministic mock model of the `mock` feature, which needs no model files.
+    #[cfg(feature = "mock")]
+    Mock,
+}
+
+impl EmbeddingModel {
+    /// All the text models available, the embedded ones first.
+    pub fn all() -> Vec<Self> {
+        let models = EmbeddedModel::ALL
+            .iter()
+            .cloned()
+            .map(Self::Embedded)
+            .chain(runtime_models().iter().map(Self::Runtime));
+
+        #[cfg(feature = "mock")]
+        let models = models.chain(std::iter::once(Self::Mock));
+
+        models.collect()
+    }
+
+    /// The name of the model, as used in the requests.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Embedded(model) => model.name(),
+            Self::Runtime(model) => model.name(),
+            #[cfg(feature = "mock")]
+            Self::Mock => transform::models::mock::NAME,
         }
-    };
+    }
+
+    /// Find an available model by its name.
+    pub fn from_name(name: &str) -> Result<Self, EmbedderAPIError> {
+        Self::all()
+            .into_iter()
+            .find(|model| model.name() == name)
+            .ok_or_else(|| EmbedderAPIError::UnknownModel(name.to_owned()))
+    }
+}
+
+impl Serialize for EmbeddingModel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for EmbeddingModel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        Self::from_name(&name).map_err(serde::de::Error::custom)
+    }
+}
+
+impl EmbeddedModel {
+    /// Get the loaded model, loading it on its first use.
+    // No models may be embedded in this build, leaving the `match` empty.
+    #[allow(unreachable_code)]
+    fn embedder(&self) -> Result<&'static dyn DynEmbedder, EmbedderError> {
+        embedder_lib::embedded_models!(
+            text,
+            dispatch_model!(self, singleton, |model| Ok(model as &dyn DynEmbedder),)
+        )
+    }
+}
+
+impl EmbeddingModel {
+    /// Get the loaded model, loading it on its first use.
+    pub fn embedder(&self) -> Result<&'static dyn DynEmbedder, EmbedderAPIError> {
+        let embedder = match self {
+            Self::Embedded(model) => model.embedder(),
+            Self::Runtime(model) => Ok(&**model.model()),
+            #[cfg(feature = "mock")]
+            Self::Mock => {
+                transform::models::mock::Model::singleton().map(|model| model as &dyn DynEmbedder)
+            }
+        };
+
+        embedder.map_err(EmbedderAPIError::EmbedderError)
+    }
+
+    /// Reject sparse models, for the endpoints that only produce dense or token-level
+    /// embeddings; only `/embed` supports them.
+    pub fn ensure_dense(&self) -> Result<(), EmbedderAPIError> {
+        check_dense(self.name(), self.kind()?)
+    }
+}
+
+/// Reject the [`ModelKind::Sparse`] models, see [`EmbeddingModel::ensure_dense`].
+fn check_dense(name: &str, kind: ModelKind) -> Result<(), EmbedderAPIError> {
+    match kind {
+        ModelKind::Sparse => Err(EmbedderAPIError::InvalidOptions(format!(
+            "'{name}' is a sparse model, which is only supported by `/embed`."
+        ))),
+        _ => Ok(()),
+    }
 }
 
 impl EmbeddingModel {
-    pass_through_method!(embed_to_array() -> ndarray::Array2<f32>);
-    pass_through_method!(embed_to_vec() -> Vec<Embedding>);
+    pass_through_method!(dyn: embed_to_array(documents: Vec<String>, batch_size: Option<usize>) -> ndarray::Array2<f32>);
+    pass_through_method!(dyn: embed_with_options(documents: Vec<String>, batch_size: Option<usize>, options: &EmbedOptions) -> ndarray::Array2<f32>);
+    pass_through_method!(dyn: embed_to_pooled_array(documents: Vec<String>, batch_size: Option<usize>) -> ndarray::Array2<f32>);
+    pass_through_method!(dyn: embed_to_sparse(documents: Vec<String>, batch_size: Option<usize>) -> Vec<SparseEmbedding>);
+    pass_through_method!(dyn: embed_to_token_arrays(documents: Vec<String>, batch_size: Option<usize>) -> Vec<ndarray::Array2<f32>>);
+    pass_through_method!(dyn: tokenize(documents: &[String], add_special_tokens: bool) -> Vec<TokenizedDocument>);
+    pass_through_method!(dyn: tokenize_with_options(documents: &[String], input_type: Option<transform::InputType>) -> Vec<TokenizedDocument>);
+    pass_through_method!(dyn: detokenize(ids: &[Vec<u32>], skip_special_tokens: bool) -> Vec<String>);
+    pass_through_method!(dyn: infallible decode_terms(indices: &[u32]) -> Vec<String>);
+    pass_through_method!(dyn: infallible kind() -> ModelKind);
+    pass_through_method!(dyn: infallible output_selection() -> Option<OutputSelection>);
+    pass_through_method!(dyn: infallible max_input_length() -> Option<usize>);
+    pass_through_method!(dyn: infallible pooling() -> Option<fastembed::Pooling>);
+    pass_through_method!(dyn: infallible token_pooling() -> Option<Pooling>);
+    pass_through_method!(dyn: infallible supported_pooling() -> Vec<Pooling>);
+    pass_through_method!(dyn: infallible dimension() -> Option<usize>);
+    pass_through_method!(dyn: infallible matryoshka_dimensions() -> Vec<usize>);
+    pass_through_method!(dyn: infallible prompts() -> PromptTemplates<'static>);
+    pass_through_method!(dyn: infallible fingerprint() -> Option<String>);
+    pass_through_method!(dyn: infallible embedded_size() -> Option<EmbeddedSize>);
 }
 
 #[derive(Debug, Deserialize)]
 pub struct EmbedQuery {
-    output: OutputType,
+    pub output: OutputType,
 }
 
 #[derive(Debug, Deserialize)]
@@ -81,15 +204,287 @@ pub struct EmbedRequest {
     #[serde(default)]
     batch_size: Option<usize>,
     documents: Vec<String>,
+    #[serde(default)]
+    granularity: Granularity,
+    /// Apply the prefix the model expects for this type of input.
+    #[serde(default)]
+    input_type: Option<InputType>,
+    /// Include the vocabulary terms of the sparse embeddings.
+    #[serde(default)]
+    return_terms: bool,
+    /// Override the default pooling of the model.
+    #[serde(default)]
+    pooling: Option<Pooling>,
+    #[serde(default)]
+    normalization: Normalization,
+    /// Truncate the embeddings to this number of dimensions, if the model supports it.
+    #[serde(default)]
+    dimensions: Option<usize>,
+    #[serde(default)]
+    precision: Precision,
+    /// The calibrated `[min, max]` range for `int8` precision, shared by all the embeddings.
+    #[serde(default)]
+    int8_range: Option<(f32, f32)>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct EmbedResponse<T: Serialize> {
     model: EmbeddingModel,
+    /// The fingerprint of the verified model files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<String>,
+    duration: f32,
+    precision: Precision,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quantization: Option<QuantizationParameters>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    input_type: Option<InputType>,
+    /// The prefix prepended to each of the documents, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt: Option<&'static str>,
+    embeddings: T,
+}
+
+/// A token of a document, as returned alongside the token-level embeddings.
+#[derive(Debug, Serialize)]
+pub struct TokenSpan {
+    id: u32,
+    token: String,
+    special: bool,
+    /// The character offsets in the document; special tokens do not have any.
+    start: Option<usize>,
+    end: Option<usize>,
+}
+
+impl From<Token> for TokenSpan {
+    fn from(token: Token) -> Self {
+        let (start, end) = match token.special {
+            true => (None, None),
+            false => (Some(token.offsets.0), Some(token.offsets.1)),
+        };
+
+        Self {
+            id: token.id,
+            token: token.token,
+            special: token.special,
+            start,
+            end,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenEmbedResponse<T: Serialize> {
+    model: EmbeddingModel,
+    /// The fingerprint of the verified model files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<String>,
     duration: f32,
+    precision: Precision,
+    tokens: Vec<Vec<TokenSpan>>,
+    /// The number of tokens of each document, if the embeddings are padded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lengths: Option<Vec<usize>>,
     embeddings: T,
 }
 
+/// Pad the token-level embeddings of each document with zeros into a single 3-D array.
+fn pad_token_arrays(arrays: &[ndarray::Array2<f32>]) -> ndarray::Array3<f32> {
+    let tokens = arrays.iter().map(|array| array.nrows()).max().unwrap_or(0);
+    let dimensions = arrays.first().map(|array| array.ncols()).unwrap_or(0);
+
+    let mut padded = ndarray::Array3::zeros((arrays.len(), tokens, dimensions));
+    padded
+        .outer_iter_mut()
+        .zip(arrays)
+        .for_each(|(mut target, array)| {
+            target
+                .slice_mut(ndarray::s![..array.nrows(), ..])
+                .assign(array)
+        });
+
+    padded
+}
+
+/// The `embed` endpoint for `granularity` of `token`, returning the embeddings of each token.
+async fn embed_tokens(
+    output: OutputType,
+    request: EmbedRequest,
+) -> Result<Json<serde_json::Value>, EmbedderAPIError> {
+    let start = Instant::now();
+
+    if request.pooling.is_some()
+        || request.dimensions.is_some()
+        || request.input_type.is_some()
+        || request.return_terms
+    {
+        return Err(EmbedderAPIError::InvalidOptions(
+            "`pooling`, `dimensions`, `input_type` and `return_terms` cannot be used with token-level embeddings."
+                .to_owned(),
+        ));
+    }
+    if !matches!(request.precision, Precision::Float32) {
+        return Err(EmbedderAPIError::InvalidOptions(
+            "token-level embeddings are only available in `float32` precision.".to_owned(),
+        ));
+    }
+
+    // Clone the model for logging only
+    let model = request.model.clone();
+    let normalization = request.normalization;
+    let batch_size = request
+        .batch_size
+        .unwrap_or_else(|| calculate_default_batch_size(request.documents.len()));
+    let (tokenized, arrays, fingerprint) = spawn_blocking(move || {
+        eprintln!(
+            "Embedding tokens of {} documents to {:?} with batch size of {}...",
+            request.documents.len(),
+            output,
+            batch_size,
+        );
+        let tokenized = request.model.tokenize(&request.documents, true)?;
+        let arrays = request
+            .model
+            .embed_to_token_arrays(request.documents, Some(batch_size))?
+            .into_iter()
+            .map(|array| normalization.apply(array))
+            .collect::<Vec<_>>();
+
+        Ok((tokenized, arrays, request.model.fingerprint()?))
+    })
+    .await?;
+    let duration = start.elapsed().as_secs_f32();
+
+    let lengths = arrays.iter().map(|array| array.nrows()).collect();
+    let (lengths, embeddings) = match output {
+        OutputType::Json => (
+            None,
+            serde_json::to_value(
+                arrays
+                    .iter()
+                    .map(|array| {
+                        array
+                            .rows()
+                            .into_iter()
+                            .map(|row| row.to_vec())
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>(),
+            )?,
+        ),
+        OutputType::Array => (
+            Some(lengths),
+            serde_json::to_value(pad_token_arrays(&arrays))?,
+        ),
+        OutputType::Base64 => (
+            Some(lengths),
+            serde_json::to_value(Base64Array::from(&pad_token_arrays(&arrays)))?,
+        ),
+        OutputType::Pickle => unreachable!("Pickle output is rejected above."),
+    };
+
+    TokenEmbedResponse {
+        model,
+        fingerprint,
+        duration,
+        precision: Precision::Float32,
+        tokens: tokenized
+            .into_iter()
+            .map(|document| document.tokens.into_iter().map(TokenSpan::from).collect())
+            .collect(),
+        lengths,
+        embeddings,
+    }
+    .to_json_response()
+}
+
+/// A sparse embedding, keeping only the non-zero dimensions of the vocabulary.
+#[derive(Debug, Serialize)]
+pub struct SparseVector {
+    indices: Vec<u32>,
+    values: Vec<f32>,
+    /// The vocabulary terms of the `indices`, if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    terms: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SparseEmbedResponse {
+    model: EmbeddingModel,
+    /// The fingerprint of the verified model files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<String>,
+    duration: f32,
+    kind: &'static str,
+    embeddings: Vec<SparseVector>,
+}
+
+/// The `embed` endpoint for sparse models, returning the `indices` and `values` of each document.
+async fn embed_sparse(
+    output: OutputType,
+    request: EmbedRequest,
+) -> Result<Json<serde_json::Value>, EmbedderAPIError> {
+    let start = Instant::now();
+
+    if !matches!(output, OutputType::Json) {
+        return Err(EmbedderAPIError::InvalidOptions(
+            "sparse embeddings are only available as `json` output.".to_owned(),
+        ));
+    }
+    if request.pooling.is_some()
+        || request.dimensions.is_some()
+        || request.input_type.is_some()
+        || !matches!(request.granularity, Granularity::Document)
+        || !matches!(request.precision, Precision::Float32)
+    {
+        return Err(EmbedderAPIError::InvalidOptions(
+            "`pooling`, `dimensions`, `input_type`, `granularity` and `precision` cannot be used with sparse models."
+                .to_owned(),
+        ));
+    }
+
+    // Clone the model for logging only
+    let model = request.model.clone();
+    let batch_size = request
+        .batch_size
+        .unwrap_or_else(|| calculate_default_batch_size(request.documents.len()));
+    let (embeddings, fingerprint) = spawn_blocking(move || {
+        eprintln!(
+            "Embedding {} documents to sparse vectors with batch size of {}...",
+            request.documents.len(),
+            batch_size,
+        );
+        let embeddings = request
+            .model
+            .embed_to_sparse(request.documents, Some(batch_size))?
+            .into_iter()
+            .map(|embedding| {
+                Ok(SparseVector {
+                    terms: request
+                        .return_terms
+                        .then(|| request.model.decode_terms(&embedding.indices))
+                        .transpose()?,
+                    indices: embedding.indices,
+                    values: embedding.values,
+                })
+            })
+            .collect::<Result<Vec<_>, EmbedderAPIError>>()?;
+
+        Ok((embeddings, request.model.fingerprint()?))
+    })
+    .await?;
+    let duration = start.elapsed().as_secs_f32();
+
+    SparseEmbedResponse {
+        model,
+        fingerprint,
+        duration,
+        kind: ModelKind::Sparse.name(),
+        embeddings,
+    }
+    .to_json_response()
+}
+
 /// The main `embed` endpoint, converting `documents` into `embeddings`.
 pub async fn embed(
     Query(query): Query<EmbedQuery>,
@@ -97,39 +492,192 @@ pub async fn embed(
 ) -> Result<Json<serde_json::Value>, EmbedderAPIError> {
     let start = Instant::now();
 
+    if let OutputType::Pickle = query.output {
+        return Err(EmbedderAPIError::NotImplemented("Pickle output".to_owned()));
+    }
+    if let Some((min, max)) = request.int8_range {
+        quantize::check_int8_range(min, max).map_err(EmbedderAPIError::InvalidOptions)?;
+    }
+
+    let kind = {
+        let model = request.model.clone();
+        spawn_blocking(move || model.kind()).await?
+    };
+    if let ModelKind::Sparse = kind {
+        return embed_sparse(query.output, request).await;
+    }
+
+    if let Granularity::Token = request.granularity {
+        return embed_tokens(query.output, request).await;
+    }
+
     // Clone the model for logging only
     let model = request.model.clone();
-    macro_rules! map_output_type_to_method {
-        ($(OutputType::$variant:ident => $method:ident),*$(,)?) => {
-            match query.output {
-                $(
-                    OutputType::$variant => {
-                        let batch_size = request.batch_size.unwrap_or_else(|| calculate_default_batch_size(request.documents.len()));
-                        let embeddings = tokio::task::spawn_blocking(move || {
-                            eprintln!(
-                                "Embedding {} documents to {} with batch size of {}...",
-                                request.documents.len(),
-                                stringify!($variant),
-                                batch_size,
-                            );
-                            request.model.$method(request.documents, Some(batch_size))
-                        })
-                        .await
-                        .map_err(|err| EmbedderAPIError::ConcurrencyError(err.to_string()))??;
-                        EmbedResponse {
-                            model,
-                            duration: start.elapsed().as_secs_f32(),
-                            embeddings, // Use the default `ndarray` serialization
-                        }.to_json_response()
-                    }
-                ),*
-                OutputType::Pickle => Err(EmbedderAPIError::NotImplemented("Pickle output".to_owned())),
-            }
+    let precision = request.precision;
+    let int8_range = request.int8_range;
+    let options = EmbedOptions {
+        pooling: request.pooling,
+        normalization: request.normalization,
+        dimensions: request.dimensions,
+        input_type: request.input_type.map(transform::InputType::from),
+    };
+    let input_type = request.input_type;
+    let batch_size = request
+        .batch_size
+        .unwrap_or_else(|| calculate_default_batch_size(request.documents.len()));
+    let (embeddings, prompt, fingerprint) = spawn_blocking(move || {
+        eprintln!(
+            "Embedding {} documents to {:?} with batch size of {}...",
+            request.documents.len(),
+            query.output,
+            batch_size,
+        );
+        let prompts = request.model.prompts()?;
+        let prompt = options
+            .input_type
+            .and_then(|input_type| prompts.prompt(input_type));
+        let fingerprint = request.model.fingerprint()?;
+
+        request
+            .model
+            .embed_with_options(request.documents, Some(batch_size), &options)
+            .map(|embeddings| (embeddings, prompt, fingerprint))
+    })
+    .await?;
+    let duration = start.elapsed().as_secs_f32();
+
+    let (embeddings, quantization) = Embeddings::new(embeddings, precision, int8_range);
+
+    EmbedResponse {
+        model,
+        fingerprint,
+        duration,
+        precision,
+        quantization,
+        input_type,
+        prompt,
+        embeddings: match query.output {
+            OutputType::Json => embeddings.to_rows_value()?,
+            OutputType::Array => embeddings.to_array_value()?, // Use the default `ndarray` serialization
+            OutputType::Base64 => embeddings.to_base64_value()?,
+            OutputType::Pickle => unreachable!("Pickle output is rejected above."),
+        },
+    }
+    .to_json_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "mock")]
+    use crate::endpoints::test_utils::{mock_model, MOCK_FAIL_ON};
+    use embedder_external::axum::http::StatusCode;
+
+    #[test]
+    fn sparse_models_are_rejected() {
+        let error = check_dense("prithivida/Splade_PP_en_v1", ModelKind::Sparse)
+            .expect_err("Sparse models should be rejected.");
+        assert!(matches!(error, EmbedderAPIError::InvalidOptions(_)));
+        assert_eq!(error.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        assert!(check_dense("sentence-transformers/all-MiniLM-L6-v2", ModelKind::Dense).is_ok());
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn mock_model_is_dense() {
+        assert!(mock_model().ensure_dense().is_ok());
+    }
+
+    #[cfg(feature = "mock")]
+    fn embed_request(documents: &[&str]) -> EmbedRequest {
+        serde_json::from_value(serde_json::json!({
+            "model": mock_model().name(),
+            "documents": documents,
+        }))
+        .expect("Failed to parse the request.")
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn embed_mock_model() {
+        let query = || {
+            Query(EmbedQuery {
+                output: OutputType::Json,
+            })
         };
+
+        let Json(response) = embed(query(), Json(embed_request(&["foo bar", "baz", "foo bar"])))
+            .await
+            .expect("Failed to embed the documents.");
+        let embeddings = response["embeddings"]
+            .as_array()
+            .expect("The embeddings should be rows.");
+
+        assert_eq!(response["model"], "mock");
+        assert_eq!(embeddings.len(), 3);
+        assert_eq!(embeddings[0], embeddings[2]);
+        assert_ne!(embeddings[0], embeddings[1]);
+        embeddings.iter().for_each(|row| {
+            let row = row.as_array().expect("Each embedding should be an array.");
+            let norm = row
+                .iter()
+                .filter_map(serde_json::Value::as_f64)
+                .map(|value| value * value)
+                .sum::<f64>();
+            assert_eq!(row.len(), 384);
+            assert!((norm - 1.0).abs() <= 1e-4);
+        });
+
+        // The same documents produce the same embeddings in any request.
+        let Json(again) = embed(query(), Json(embed_request(&["baz"])))
+            .await
+            .expect("Failed to embed the documents.");
+        assert_eq!(again["embeddings"][0], embeddings[1]);
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn embed_mock_model_failure() {
+        let error = embed(
+            Query(EmbedQuery {
+                output: OutputType::Json,
+            }),
+            Json(embed_request(&[
+                "fine",
+                &format!("this will {MOCK_FAIL_ON}"),
+            ])),
+        )
+        .await
+        .expect_err("The injected failure should fail the request.");
+
+        assert!(matches!(
+            error,
+            EmbedderAPIError::EmbedderError(EmbedderError::FastEmbedError(_))
+        ));
+        assert_eq!(error.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
     }
 
-    map_output_type_to_method!(
-        OutputType::Json => embed_to_vec,
-        OutputType::Array => embed_to_array,
-    )
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn embed_tokens_rejects_return_terms() {
+        let request = serde_json::from_value(serde_json::json!({
+            "model": mock_model().name(),
+            "documents": ["foo bar"],
+            "granularity": "token",
+            "return_terms": true,
+        }))
+        .expect("Failed to parse the request.");
+
+        let error = embed(
+            Query(EmbedQuery {
+                output: OutputType::Json,
+            }),
+            Json(request),
+        )
+        .await
+        .expect_err("`return_terms` should be rejected for token-level embeddings.");
+
+        assert!(matches!(error, EmbedderAPIError::InvalidOptions(_)));
+    }
 }
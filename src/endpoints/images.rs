@@ -0,0 +1,246 @@
+//! The `embed_images` endpoint, converting `images` into `embeddings` in the same vector space
+//! as their paired text model.
+
+use embedder_err::EmbedderAPIError;
+use embedder_external::axum::{
+    async_trait,
+    extract::{FromRequest, Multipart, Query, Request},
+    http::header::CONTENT_TYPE,
+    Json,
+};
+use embedder_external::serde::{Deserialize, Serialize};
+use embedder_external::{ndarray, serde_json};
+use embedder_lib::quantize;
+use embedder_lib::transform::{
+    models::{EmbeddedSize, HasEmbeddedSize},
+    CanEmbedImage, Normalization,
+};
+use tokio::time::Instant;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use super::{EmbedQuery, EmbeddingModel, OutputType};
+use crate::common::{calculate_default_batch_size, spawn_blocking, ToJsonResponse};
+use crate::encoding::{Embeddings, Precision, QuantizationParameters};
+
+embedder_lib::embedded_models!(image, declare_models!(ImageEmbeddingModel));
+
+impl ImageEmbeddingModel {
+    /// The name of the paired text model, as declared in `models.toml`, without loading the
+    /// model.
+    // No image models may be enabled in this build, leaving the `match` empty.
+    #[allow(unreachable_code)]
+    pub fn text_model_name(&self) -> Option<&'static str> {
+        embedder_lib::embedded_models!(image, dispatch_model!(self, const TEXT_MODEL,))
+    }
+
+    /// The paired text model, if it is enabled in this build.
+    pub fn text_model(&self) -> Option<EmbeddingModel> {
+        self.text_model_name()
+            .and_then(|name| EmbeddingModel::from_name(name).ok())
+    }
+}
+
+impl ImageEmbeddingModel {
+    pass_through_method!(image: embed_images(images: &[Vec<u8>], batch_size: Option<usize>, normalization: Normalization) -> ndarray::Array2<f32>);
+    pass_through_method!(image: infallible fingerprint() -> Option<String>);
+    pass_through_method!(image: infallible embedded_size() -> Option<EmbeddedSize>);
+    load_method!(image);
+}
+
+/// The options of the request, shared by the JSON and the multipart forms.
+#[derive(Debug, Deserialize)]
+pub struct EmbedImagesOptions {
+    model: ImageEmbeddingModel,
+    #[serde(default)]
+    batch_size: Option<usize>,
+    #[serde(default)]
+    normalization: Normalization,
+    #[serde(default)]
+    precision: Precision,
+    /// The calibrated `[min, max]` range for `int8` precision, shared by all the embeddings.
+    #[serde(default)]
+    int8_range: Option<(f32, f32)>,
+}
+
+/// The JSON form of the request, with the images encoded in base64.
+#[derive(Debug, Deserialize)]
+struct EmbedImagesJson {
+    #[serde(flatten)]
+    options: EmbedImagesOptions,
+    /// Base64 encoded images, optionally as `data:` URLs.
+    images: Vec<String>,
+}
+
+/// A request to embed images, either as a JSON body with base64 encoded `images`, or as a
+/// `multipart/form-data` body with one `image` part per image and the options as text parts.
+#[derive(Debug)]
+pub struct EmbedImagesRequest {
+    options: EmbedImagesOptions,
+    images: Vec<Vec<u8>>,
+}
+
+impl EmbedImagesRequest {
+    /// Decode the base64 images of the JSON form.
+    fn from_json(request: EmbedImagesJson) -> Result<Self, EmbedderAPIError> {
+        let images = request
+            .images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| {
+                // Strip the `data:image/...;base64,` prefix of data URLs.
+                let data = match image.strip_prefix("data:") {
+                    Some(url) => url.split_once(',').map_or(url, |(_, data)| data),
+                    None => image,
+                };
+
+                STANDARD.decode(data).map_err(|err| {
+                    EmbedderAPIError::BadRequest(format!("images.{index} is not base64: {err}"))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            options: request.options,
+            images,
+        })
+    }
+
+    /// Collect the `image` parts and the options of the multipart form.
+    async fn from_multipart(mut multipart: Multipart) -> Result<Self, EmbedderAPIError> {
+        let mut images = Vec::new();
+        let mut options = serde_json::Map::new();
+
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|err| EmbedderAPIError::BadRequest(err.body_text()))?
+        {
+            let name = field.name().unwrap_or_default().to_owned();
+            match name.as_str() {
+                "image" | "images" => images.push(
+                    field
+                        .bytes()
+                        .await
+                        .map_err(|err| EmbedderAPIError::BadRequest(err.body_text()))?
+                        .to_vec(),
+                ),
+                _ => {
+                    let text = field
+                        .text()
+                        .await
+                        .map_err(|err| EmbedderAPIError::BadRequest(err.body_text()))?;
+
+                    // Numbers and arrays such as `int8_range` are given as JSON; anything else
+                    // is taken as a string.
+                    let value = serde_json::from_str(&text)
+                        .unwrap_or_else(|_| serde_json::Value::String(text));
+                    options.insert(name, value);
+                }
+            }
+        }
+
+        Ok(Self {
+            options: serde_json::from_value(serde_json::Value::Object(options))
+                .map_err(|err| EmbedderAPIError::BadRequest(err.to_string()))?,
+            images,
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequest<S> for EmbedImagesRequest {
+    type Rejection = EmbedderAPIError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_multipart = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("multipart/form-data"));
+
+        if is_multipart {
+            let multipart = Multipart::from_request(request, state)
+                .await
+                .map_err(|err| EmbedderAPIError::BadRequest(err.body_text()))?;
+            Self::from_multipart(multipart).await
+        } else {
+            let Json(request) = Json::<EmbedImagesJson>::from_request(request, state)
+                .await
+                .map_err(|err| EmbedderAPIError::BadRequest(err.body_text()))?;
+            Self::from_json(request)
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbedImagesResponse {
+    model: ImageEmbeddingModel,
+    /// The text model sharing the vector space, to be used with `/embed`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text_model: Option<EmbeddingModel>,
+    duration: f32,
+    precision: Precision,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quantization: Option<QuantizationParameters>,
+    embeddings: serde_json::Value,
+}
+
+/// Embed the images into the vector space shared with their paired text model.
+pub async fn embed_images(
+    Query(query): Query<EmbedQuery>,
+    request: EmbedImagesRequest,
+) -> Result<Json<serde_json::Value>, EmbedderAPIError> {
+    let start = Instant::now();
+
+    if let OutputType::Pickle = query.output {
+        return Err(EmbedderAPIError::NotImplemented("Pickle output".to_owned()));
+    }
+
+    let EmbedImagesRequest { options, images } = request;
+    // Clone the model for logging only
+    let model = options.model.clone();
+    let precision = options.precision;
+    let int8_range = options.int8_range;
+    if let Some((min, max)) = int8_range {
+        quantize::check_int8_range(min, max).map_err(EmbedderAPIError::InvalidOptions)?;
+    }
+    let normalization = options.normalization;
+    let batch_size = options
+        .batch_size
+        .unwrap_or_else(|| calculate_default_batch_size(images.len()));
+    let (embeddings, text_model) = spawn_blocking(move || {
+        eprintln!(
+            "Embedding {} images to {:?} with batch size of {}...",
+            images.len(),
+            query.output,
+            batch_size,
+        );
+
+        let text_model = options.model.text_model();
+        options
+            .model
+            .embed_images(&images, Some(batch_size), normalization)
+            .map(|embeddings| (embeddings, text_model))
+    })
+    .await?;
+    let duration = start.elapsed().as_secs_f32();
+
+    let (embeddings, quantization) = Embeddings::new(embeddings, precision, int8_range);
+
+    EmbedImagesResponse {
+        model,
+        text_model,
+        duration,
+        precision,
+        quantization,
+        embeddings: match query.output {
+            OutputType::Json => embeddings.to_rows_value()?,
+            OutputType::Array => embeddings.to_array_value()?,
+            OutputType::Base64 => embeddings.to_base64_value()?,
+            OutputType::Pickle => unreachable!("Pickle output is rejected above."),
+        },
+    }
+    .to_json_response()
+}
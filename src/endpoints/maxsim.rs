@@ -0,0 +1,193 @@
+//! The `maxsim` endpoint, scoring `documents` against a `query` by late interaction.
+
+use embedder_err::{EmbedderAPIError, EmbedderError};
+use embedder_external::axum::Json;
+use embedder_external::ndarray;
+use embedder_external::serde::{Deserialize, Serialize};
+use embedder_lib::transform::{self, InputType, Normalization};
+use std::borrow::Cow;
+use std::sync::Arc;
+use tokio::time::Instant;
+
+use super::EmbeddingModel;
+use crate::cache::TokenCache;
+use crate::common::{calculate_default_batch_size, spawn_blocking};
+
+#[derive(Debug, Deserialize)]
+pub struct MaxSimRequest {
+    model: EmbeddingModel,
+    #[serde(default)]
+    batch_size: Option<usize>,
+    query: String,
+    documents: Vec<String>,
+    /// Keep the token-level embeddings of the documents for subsequent requests.
+    #[serde(default)]
+    cache: bool,
+    /// Only return the best `top_k` documents in the `ranking`.
+    #[serde(default)]
+    top_k: Option<usize>,
+}
+
+/// A document in the ranking, identified by its index in the request.
+#[derive(Debug, Clone, Serialize)]
+pub struct RankedDocument {
+    index: usize,
+    score: f32,
+}
+
+impl RankedDocument {
+    /// Rank the documents by descending score, keeping only the best `top_k` if given.
+    pub fn rank(scores: &[f32], top_k: Option<usize>) -> Vec<Self> {
+        let mut ranking = scores
+            .iter()
+            .enumerate()
+            .map(|(index, score)| Self {
+                index,
+                score: *score,
+            })
+            .collect::<Vec<_>>();
+        ranking.sort_by(|a, b| b.score.total_cmp(&a.score));
+        if let Some(top_k) = top_k {
+            ranking.truncate(top_k);
+        }
+
+        ranking
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaxSimResponse {
+    model: EmbeddingModel,
+    duration: f32,
+    /// The scores of the documents, in the order of the request.
+    scores: Vec<f32>,
+    /// The documents sorted by descending score.
+    ranking: Vec<RankedDocument>,
+    /// The number of documents found in the cache.
+    cache_hits: usize,
+}
+
+/// Get the normalized token-level embeddings of the documents, using the cache if requested.
+///
+/// The documents are cached without the document prefix of the model, which is applied before
+/// embedding.
+///
+/// Returns the arrays in the order of the documents, and the number of cache hits.
+fn document_token_arrays(
+    model: &EmbeddingModel,
+    documents: Vec<String>,
+    batch_size: usize,
+    cache: bool,
+) -> Result<(Vec<Arc<ndarray::Array2<f32>>>, usize), EmbedderAPIError> {
+    let cached = documents
+        .iter()
+        .map(|document| {
+            cache
+                .then(|| TokenCache::get().lookup(model.name(), document))
+                .flatten()
+        })
+        .collect::<Vec<_>>();
+
+    let (missing_indices, missing_documents): (Vec<_>, Vec<_>) = documents
+        .into_iter()
+        .zip(cached.iter())
+        .enumerate()
+        .filter_map(|(idx, (document, array))| array.is_none().then_some((idx, document)))
+        .unzip();
+    let cache_hits = cached.len() - missing_indices.len();
+
+    let mut arrays = cached;
+    if !missing_documents.is_empty() {
+        let prompted = model
+            .prompts()?
+            .apply(&missing_documents, Some(InputType::Document))
+            .into_iter()
+            .map(Cow::into_owned)
+            .collect::<Vec<_>>();
+        let embedded = model.embed_to_token_arrays(prompted, Some(batch_size))?;
+
+        for ((idx, document), array) in missing_indices
+            .into_iter()
+            .zip(missing_documents)
+            .zip(embedded)
+        {
+            let array = Arc::new(Normalization::L2.apply(array));
+            if cache {
+                TokenCache::get().insert(model.name(), document, Arc::clone(&array));
+            }
+            arrays[idx] = Some(array);
+        }
+    }
+
+    arrays
+        .into_iter()
+        .collect::<Option<Vec<_>>>()
+        .map(|arrays| (arrays, cache_hits))
+        .ok_or_else(|| {
+            EmbedderAPIError::from(EmbedderError::OutputTransformError(
+                "No token-level embeddings found for some of the documents.".to_owned(),
+            ))
+        })
+}
+
+/// Score the `documents` against the `query` with ColBERT MaxSim.
+pub async fn maxsim(
+    Json(request): Json<MaxSimRequest>,
+) -> Result<Json<MaxSimResponse>, EmbedderAPIError> {
+    let start = Instant::now();
+
+    let model = request.model.clone();
+    let top_k = request.top_k;
+    let batch_size = request
+        .batch_size
+        .unwrap_or_else(|| calculate_default_batch_size(request.documents.len()));
+    let (scores, cache_hits) = spawn_blocking(move || {
+        eprintln!(
+            "Scoring {} documents by MaxSim with batch size of {}...",
+            request.documents.len(),
+            batch_size,
+        );
+        request.model.ensure_dense()?;
+        let query = request
+            .model
+            .prompts()?
+            .apply(&[request.query], Some(InputType::Query))
+            .into_iter()
+            .map(Cow::into_owned)
+            .collect::<Vec<_>>();
+        let query = request
+            .model
+            .embed_to_token_arrays(query, None)?
+            .pop()
+            .map(|array| Normalization::L2.apply(array))
+            .ok_or_else(|| {
+                EmbedderError::OutputTransformError(
+                    "No token-level embeddings found for the query.".to_owned(),
+                )
+            })?;
+
+        let (documents, cache_hits) =
+            document_token_arrays(&request.model, request.documents, batch_size, request.cache)?;
+
+        Ok((
+            documents
+                .iter()
+                .map(|document| transform::maxsim(query.view(), document.view()))
+                .collect::<Vec<_>>(),
+            cache_hits,
+        ))
+    })
+    .await?;
+    let duration = start.elapsed().as_secs_f32();
+
+    let ranking = RankedDocument::rank(&scores, top_k);
+
+    Ok(Json(MaxSimResponse {
+        model,
+        duration,
+        scores,
+        ranking,
+        cache_hits,
+    }))
+}
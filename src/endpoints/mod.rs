@@ -1,8 +1,177 @@
 #![allow(unused_imports)]
 //! The functions that are called when the server receives a request.
 
+/// Declare the request enum of the models embedded in this build, with one variant per model
+/// serialized as its name; invoked through `embedder_lib::embedded_models!`.
+macro_rules! declare_models {
+    (
+        $(#[$meta:meta])*
+        $enum:ident
+        [$(($variant:ident, $name:literal, $module:ident)),*$(,)?]
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub enum $enum {
+            $(
+                #[serde(rename = $name)]
+                $variant,
+            )*
+        }
+
+        impl $enum {
+            /// All the models enabled in this build.
+            pub const ALL: &'static [Self] = &[$(Self::$variant),*];
+
+            /// The name of the model, as used in the requests.
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    $(Self::$variant => $name,)*
+                }
+            }
+        }
+    };
+}
+
+/// Dispatch a method call to the model of each variant of a request enum declared by
+/// `declare_models!`, or pass the model itself to `$wrap` if no method is given; invoked through
+/// `embedder_lib::embedded_models!`.
+///
+/// With `const`, the constant of that name in the module of each model is returned instead,
+/// without loading the model. With `singleton`, `$wrap` is given the `&'static` reference to the
+/// model rather than a new `Arc`.
+macro_rules! dispatch_model {
+    (
+        $self:tt, const $constant:ident,
+        [$(($variant:ident, $name:literal, $module:ident)),*$(,)?]
+    ) => {
+        match *$self {
+            $(
+                Self::$variant => embedder_lib::transform::models::$module::$constant,
+            )*
+        }
+    };
+    (
+        $self:tt, singleton, $wrap:expr,
+        [$(($variant:ident, $name:literal, $module:ident)),*$(,)?]
+    ) => {
+        match *$self {
+            $(
+                Self::$variant => embedder_lib::transform::models::$module::Model::singleton()
+                    .and_then($wrap),
+            )*
+        }
+    };
+    (
+        $self:tt, $wrap:expr,
+        [$(($variant:ident, $name:literal, $module:ident)),*$(,)?]
+    ) => {
+        match *$self {
+            $(
+                Self::$variant => embedder_lib::transform::models::$module::Model::new()
+                    .and_then($wrap),
+            )*
+        }
+    };
+    (
+        $self:tt, $method:ident $args:tt, $wrap:expr,
+        [$(($variant:ident, $name:literal, $module:ident)),*$(,)?]
+    ) => {
+        match *$self {
+            $(
+                Self::$variant => embedder_lib::transform::models::$module::Model::new()
+                    .and_then(|model| ($wrap)(model.$method $args)),
+            )*
+        }
+    };
+}
+
+/// Forward a method of the models to a request enum, converting the errors into
+/// [`EmbedderAPIError`]; methods that cannot fail are marked `infallible`.
+///
+/// The models are loaded through the `embedder()` method of the enum for `dyn`, or dispatched
+/// by `embedder_lib::embedded_models!` for any other category, e.g. `reranker`.
+macro_rules! pass_through_method {
+    ($source:tt: infallible $method:ident $args:tt -> $output:ty) => {
+        pass_through_method!(@impl $source, Ok, $method $args -> $output);
+    };
+    ($source:tt: $method:ident $args:tt -> $output:ty) => {
+        pass_through_method!(@impl $source, |result| result, $method $args -> $output);
+    };
+    (
+        @impl dyn, $wrap:expr,
+        $method:ident($($arg:ident: $arg_ty:ty),*$(,)?) -> $output:ty
+    ) => {
+        pub fn $method(
+            &self,
+            $($arg: $arg_ty),*
+        ) -> Result<$output, EmbedderAPIError> {
+            let embedder = self.embedder()?;
+            let result: Result<$output, _> = ($wrap)(embedder.$method($($arg),*));
+
+            result.map_err(EmbedderAPIError::EmbedderError)
+        }
+    };
+    (
+        @impl $category:ident, $wrap:expr,
+        $method:ident($($arg:ident: $arg_ty:ty),*$(,)?) -> $output:ty
+    ) => {
+        // No models of the category may be enabled in this build, leaving the `match` empty.
+        #[allow(unreachable_code, unused_variables)]
+        pub fn $method(
+            &self,
+            $($arg: $arg_ty),*
+        ) -> Result<$output, EmbedderAPIError> {
+            let result: Result<$output, _> = embedder_lib::embedded_models!(
+                $category,
+                dispatch_model!(self, $method($($arg),*), $wrap,)
+            );
+
+            result.map_err(EmbedderAPIError::EmbedderError)
+        }
+    };
+}
+
+/// Declare the `load` method of a request enum, which loads its model ahead of its first use;
+/// `dyn` enums load their models with their `embedder()` method instead.
+macro_rules! load_method {
+    ($category:ident) => {
+        /// Load the model, if it has not been loaded yet.
+        // No models of the category may be enabled in this build, leaving the `match` empty.
+        #[allow(unreachable_code)]
+        pub fn load(&self) -> Result<(), EmbedderAPIError> {
+            let result: Result<(), _> =
+                embedder_lib::embedded_models!($category, dispatch_model!(self, |_| Ok(()),));
+
+            result.map_err(EmbedderAPIError::EmbedderError)
+        }
+    };
+}
+
 mod embed;
 pub use embed::*;
 
+mod images;
+pub use images::*;
+
+mod maxsim;
+pub use maxsim::*;
+
+mod models;
+pub use models::*;
+
+mod rerank;
+pub use rerank::*;
+
 mod root;
 pub use root::*;
+
+mod runtime;
+pub use runtime::*;
+
+pub mod tei;
+
+#[cfg(all(test, feature = "mock"))]
+mod test_utils;
+
+mod tokenize;
+pub use tokenize::*;
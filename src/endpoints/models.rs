@@ -0,0 +1,190 @@
+//! The `models` endpoint, listing the models available on this server.
+
+use embedder_err::EmbedderAPIError;
+use embedder_external::axum::Json;
+use embedder_external::fastembed;
+use embedder_external::serde::Serialize;
+use embedder_lib::transform::{ModelKind, Pooling};
+
+use super::{EmbeddingModel, ImageEmbeddingModel, RerankerModel};
+use crate::common::spawn_blocking;
+#[cfg(feature = "status")]
+use crate::status::{ModelLoad, Status};
+
+/// The name of the default pooling method of the model, as reported to the clients.
+pub fn pooling_name(model: &EmbeddingModel) -> Result<&'static str, EmbedderAPIError> {
+    if let Some(pooling) = model.token_pooling()? {
+        return Ok(pooling.name());
+    }
+
+    Ok(match model.pooling()? {
+        Some(fastembed::Pooling::Cls) => "cls",
+        Some(fastembed::Pooling::Mean) => "mean",
+        None => "none",
+    })
+}
+
+/// Description of a model available on this server.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelDescription {
+    pub name: &'static str,
+    pub kind: &'static str,
+    pub pooling: &'static str,
+    pub supported_pooling: Vec<&'static str>,
+    pub max_input_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimension: Option<usize>,
+    pub matryoshka_dimensions: Vec<usize>,
+    pub query_prompt: Option<&'static str>,
+    pub document_prompt: Option<&'static str>,
+    /// The model embedding the other modality into the same vector space, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paired_model: Option<&'static str>,
+    /// The fingerprint of the verified model files, see `embedder_lib::integrity`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// The number of bytes of the model files embedded in the binary, compressed with the `zstd`
+    /// feature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compressed_size: Option<usize>,
+    /// The number of bytes of the model files once decompressed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decompressed_size: Option<usize>,
+    /// The ONNX output used for the embeddings of the models loaded at runtime.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_key: Option<String>,
+    /// Whether `outputKey` was `configured` or `detected` from the ONNX graph.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_key_source: Option<&'static str>,
+}
+
+impl ModelDescription {
+    /// Describe the model; this loads the model if it has not been loaded yet.
+    pub fn new(model: &EmbeddingModel) -> Result<Self, EmbedderAPIError> {
+        let prompts = model.prompts()?;
+        let embedded_size = model.embedded_size()?;
+        let output = model.output_selection()?;
+
+        Ok(Self {
+            name: model.name(),
+            kind: model.kind()?.name(),
+            pooling: pooling_name(model)?,
+            supported_pooling: model
+                .supported_pooling()?
+                .iter()
+                .map(Pooling::name)
+                .collect(),
+            max_input_length: model.max_input_length()?,
+            dimension: model.dimension()?,
+            matryoshka_dimensions: model.matryoshka_dimensions()?,
+            query_prompt: prompts.query,
+            document_prompt: prompts.document,
+            paired_model: ImageEmbeddingModel::ALL
+                .iter()
+                .find(|image_model| image_model.text_model_name() == Some(model.name()))
+                .map(ImageEmbeddingModel::name),
+            fingerprint: model.fingerprint()?,
+            compressed_size: embedded_size.map(|size| size.compressed),
+            decompressed_size: embedded_size.map(|size| size.decompressed),
+            output_key_source: output.as_ref().map(|output| output.source.name()),
+            output_key: output.map(|output| output.key),
+        })
+    }
+
+    /// Describe a cross-encoder reranking model, which produces no embeddings.
+    pub fn reranker(model: &RerankerModel) -> Result<Self, EmbedderAPIError> {
+        let embedded_size = model.embedded_size()?;
+
+        Ok(Self {
+            name: model.name(),
+            kind: ModelKind::Reranker.name(),
+            pooling: "none",
+            supported_pooling: Vec::new(),
+            max_input_length: model.max_input_length()?,
+            dimension: None,
+            matryoshka_dimensions: Vec::new(),
+            query_prompt: None,
+            document_prompt: None,
+            paired_model: None,
+            fingerprint: model.fingerprint()?,
+            compressed_size: embedded_size.map(|size| size.compressed),
+            decompressed_size: embedded_size.map(|size| size.decompressed),
+            output_key: None,
+            output_key_source: None,
+        })
+    }
+
+    /// Describe an image model, which takes images instead of documents.
+    pub fn image(model: &ImageEmbeddingModel) -> Result<Self, EmbedderAPIError> {
+        let embedded_size = model.embedded_size()?;
+
+        Ok(Self {
+            name: model.name(),
+            kind: ModelKind::Image.name(),
+            pooling: "none",
+            supported_pooling: Vec::new(),
+            max_input_length: None,
+            dimension: None,
+            matryoshka_dimensions: Vec::new(),
+            query_prompt: None,
+            document_prompt: None,
+            paired_model: model.text_model_name(),
+            fingerprint: model.fingerprint()?,
+            compressed_size: embedded_size.map(|size| size.compressed),
+            decompressed_size: embedded_size.map(|size| size.decompressed),
+            output_key: None,
+            output_key_source: None,
+        })
+    }
+
+    /// Describe all the enabled models.
+    pub fn all() -> Result<Vec<Self>, EmbedderAPIError> {
+        EmbeddingModel::all()
+            .iter()
+            .map(Self::new)
+            .chain(RerankerModel::ALL.iter().map(Self::reranker))
+            .chain(ImageEmbeddingModel::ALL.iter().map(Self::image))
+            .collect()
+    }
+}
+
+/// Load a model, recording the time and memory it took with the `status` feature.
+fn preload_model(
+    name: &'static str,
+    load: impl FnOnce() -> Result<(), EmbedderAPIError>,
+) -> Result<(), EmbedderAPIError> {
+    #[cfg(feature = "status")]
+    {
+        let (_, model_load) = ModelLoad::measure(name, load)?;
+        eprintln!("{model_load}");
+        Status::get().record_model_load(model_load);
+    }
+    #[cfg(not(feature = "status"))]
+    {
+        load()?;
+        eprintln!("Loaded {name}.");
+    }
+
+    Ok(())
+}
+
+/// Load all the enabled models ahead of their first request.
+pub fn preload_models() -> Result<(), EmbedderAPIError> {
+    for model in EmbeddingModel::all() {
+        preload_model(model.name(), || model.embedder().map(drop))?;
+    }
+    for model in RerankerModel::ALL {
+        preload_model(model.name(), || model.load())?;
+    }
+    for model in ImageEmbeddingModel::ALL {
+        preload_model(model.name(), || model.load())?;
+    }
+
+    Ok(())
+}
+
+/// List the models available on this server.
+pub async fn models() -> Result<Json<Vec<ModelDescription>>, EmbedderAPIError> {
+    spawn_blocking(ModelDescription::all).await.map(Json)
+}
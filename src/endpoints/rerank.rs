@@ -0,0 +1,80 @@
+//! The `rerank` endpoint, scoring `documents` against a `query` with a cross-encoder.
+
+use embedder_err::EmbedderAPIError;
+use embedder_external::axum::Json;
+use embedder_external::serde::{Deserialize, Serialize};
+use embedder_lib::transform::{
+    models::{EmbeddedSize, HasEmbeddedSize},
+    CanRerank,
+};
+use tokio::time::Instant;
+
+use super::RankedDocument;
+use crate::common::{calculate_default_batch_size, spawn_blocking};
+
+embedder_lib::embedded_models!(reranker, declare_models!(RerankerModel));
+
+impl RerankerModel {
+    pass_through_method!(reranker: rerank(query: String, documents: Vec<String>, batch_size: Option<usize>) -> Vec<f32>);
+    pass_through_method!(reranker: infallible max_input_length() -> Option<usize>);
+    pass_through_method!(reranker: infallible fingerprint() -> Option<String>);
+    pass_through_method!(reranker: infallible embedded_size() -> Option<EmbeddedSize>);
+    load_method!(reranker);
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RerankRequest {
+    model: RerankerModel,
+    #[serde(default)]
+    batch_size: Option<usize>,
+    query: String,
+    documents: Vec<String>,
+    /// Only return the best `top_k` documents in the `ranking`.
+    #[serde(default)]
+    top_k: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RerankResponse {
+    model: RerankerModel,
+    duration: f32,
+    /// The relevance scores of the documents, in the order of the request.
+    scores: Vec<f32>,
+    /// The documents sorted by descending score.
+    ranking: Vec<RankedDocument>,
+}
+
+/// Score the `documents` for their relevance to the `query` with a cross-encoder.
+pub async fn rerank(
+    Json(request): Json<RerankRequest>,
+) -> Result<Json<RerankResponse>, EmbedderAPIError> {
+    let start = Instant::now();
+
+    let model = request.model.clone();
+    let top_k = request.top_k;
+    let batch_size = request
+        .batch_size
+        .unwrap_or_else(|| calculate_default_batch_size(request.documents.len()));
+    let scores = spawn_blocking(move || {
+        eprintln!(
+            "Reranking {} documents with batch size of {}...",
+            request.documents.len(),
+            batch_size,
+        );
+        request
+            .model
+            .rerank(request.query, request.documents, Some(batch_size))
+    })
+    .await?;
+    let duration = start.elapsed().as_secs_f32();
+
+    let ranking = RankedDocument::rank(&scores, top_k);
+
+    Ok(Json(RerankResponse {
+        model,
+        duration,
+        scores,
+        ranking,
+    }))
+}
@@ -0,0 +1,99 @@
+//! The text models loaded at runtime from a folder of models, rather than embedded in the binary.
+//!
+//! This allows a build without any of the model features, which needs no model files to
+//! compile, to serve the models found on the host.
+
+use embedder_err::EmbedderAPIError;
+use embedder_lib::transform::{models::manifest, DynEmbedder};
+
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use super::EmbeddedModel;
+
+/// The models loaded by [`load_runtime_models`].
+static RUNTIME_MODELS: OnceLock<Vec<RuntimeModel>> = OnceLock::new();
+
+/// A text model loaded at runtime.
+#[derive(Clone)]
+pub struct RuntimeModel {
+    name: String,
+    model: Arc<dyn DynEmbedder>,
+}
+
+impl std::fmt::Debug for RuntimeModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RuntimeModel")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RuntimeModel {
+    /// The name of the model, as used in the requests.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The loaded model.
+    pub fn model(&self) -> &Arc<dyn DynEmbedder> {
+        &self.model
+    }
+}
+
+/// All the models loaded at runtime; empty if [`load_runtime_models`] was not called.
+pub fn runtime_models() -> &'static [RuntimeModel] {
+    RUNTIME_MODELS.get().map_or(&[], Vec::as_slice)
+}
+
+/// Load the models of the manifest that are found in `model_dir`, each in its `<name>`
+/// subfolder.
+///
+/// `manifest_path` defaults to the manifest the binary was built with. Models embedded in the
+/// binary are not loaded again, and models whose folder is missing or fails to load are skipped
+/// with a message, so that the server still starts with the rest.
+pub fn load_runtime_models(
+    model_dir: &Path,
+    manifest_path: Option<&Path>,
+) -> Result<(), EmbedderAPIError> {
+    let entries = match manifest_path {
+        Some(path) => manifest::read_manifest(path)?,
+        None => manifest::parse_manifest(manifest::EMBEDDED_MANIFEST)?,
+    };
+
+    let models = entries
+        .iter()
+        .filter(|entry| {
+            !EmbeddedModel::ALL
+                .iter()
+                .any(|model| model.name() == entry.name())
+        })
+        .filter(|entry| model_dir.join(entry.name()).is_dir())
+        .filter_map(|entry| match entry.load(model_dir) {
+            Ok(model) => {
+                eprintln!(
+                    "Loaded {name} from {path:?}.",
+                    name = entry.name(),
+                    path = model_dir.join(entry.name())
+                );
+
+                Some(RuntimeModel {
+                    name: entry.name().to_owned(),
+                    model: Arc::new(model),
+                })
+            }
+            Err(err) => {
+                eprintln!("Skipping {name}: {err}", name = entry.name());
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if models.is_empty() && EmbeddedModel::ALL.is_empty() {
+        eprintln!("No text models were found in {model_dir:?}.");
+    }
+
+    RUNTIME_MODELS
+        .set(models)
+        .map_err(|_| EmbedderAPIError::ConcurrencyError("Models were already loaded.".to_owned()))
+}
@@ -0,0 +1,86 @@
+//! The `embed` and `embed_all` endpoints of Text Embeddings Inference.
+
+use embedder_external::axum::{
+    extract::{Json, State},
+    response::Response,
+};
+use embedder_external::serde::Deserialize;
+use embedder_lib::Embedding;
+
+use super::{default_true, validate_length, with_fingerprint, EmbeddingModel, Input, TeiError};
+use crate::common::{calculate_default_batch_size, spawn_blocking};
+
+#[derive(Debug, Deserialize)]
+pub struct TeiEmbedRequest {
+    inputs: Input,
+    #[serde(default = "default_true")]
+    normalize: bool,
+    #[serde(default)]
+    truncate: bool,
+}
+
+/// Embed the `inputs` into one vector per input.
+pub async fn embed(
+    State(model): State<EmbeddingModel>,
+    Json(request): Json<TeiEmbedRequest>,
+) -> Result<Response, TeiError> {
+    let documents = request.inputs.into_documents()?;
+
+    spawn_blocking(move || {
+        model.ensure_dense()?;
+        if !request.truncate {
+            validate_length(&model, &documents)?;
+        }
+
+        let batch_size = Some(calculate_default_batch_size(documents.len()));
+        let embeddings = if request.normalize {
+            model.embed_to_array(documents, batch_size)?
+        } else {
+            model.embed_to_pooled_array(documents, batch_size)?
+        };
+
+        let embeddings: Vec<Embedding> = embeddings
+            .rows()
+            .into_iter()
+            .map(|row| row.to_vec())
+            .collect();
+
+        Ok(with_fingerprint(embeddings, model.fingerprint()?))
+    })
+    .await
+    .map_err(TeiError::from)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TeiEmbedAllRequest {
+    inputs: Input,
+    #[serde(default)]
+    truncate: bool,
+}
+
+/// Embed the `inputs` into one vector per token, without pooling.
+pub async fn embed_all(
+    State(model): State<EmbeddingModel>,
+    Json(request): Json<TeiEmbedAllRequest>,
+) -> Result<Response, TeiError> {
+    let documents = request.inputs.into_documents()?;
+
+    spawn_blocking(move || {
+        model.ensure_dense()?;
+        if !request.truncate {
+            validate_length(&model, &documents)?;
+        }
+
+        let batch_size = Some(calculate_default_batch_size(documents.len()));
+
+        let embeddings: Vec<Vec<Embedding>> = model
+            .embed_to_token_arrays(documents, batch_size)?
+            .into_iter()
+            .map(|tokens| tokens.rows().into_iter().map(|row| row.to_vec()).collect())
+            .collect();
+
+        Ok(with_fingerprint(embeddings, model.fingerprint()?))
+    })
+    .await
+    .map_err(TeiError::from)
+}
@@ -0,0 +1,53 @@
+//! The `info` endpoint of Text Embeddings Inference.
+
+use embedder_external::axum::extract::{Json, State};
+use embedder_external::serde::Serialize;
+
+use super::{EmbeddingModel, TeiError};
+use crate::common::spawn_blocking;
+use crate::endpoints::pooling_name;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Serialize)]
+pub struct TeiEmbeddingModelType {
+    pooling: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub enum TeiModelType {
+    #[serde(rename = "embedding")]
+    Embedding(TeiEmbeddingModelType),
+}
+
+#[derive(Debug, Serialize)]
+pub struct TeiInfoResponse {
+    model_id: &'static str,
+    /// The fingerprint of the verified model files, in place of the revision of the model.
+    model_sha: Option<String>,
+    model_dtype: &'static str,
+    model_type: TeiModelType,
+    max_input_length: Option<usize>,
+    version: &'static str,
+}
+
+/// Describe the model served under this prefix.
+///
+/// This loads the model if it has not been loaded yet.
+pub async fn info(State(model): State<EmbeddingModel>) -> Result<Json<TeiInfoResponse>, TeiError> {
+    spawn_blocking(move || {
+        Ok(TeiInfoResponse {
+            model_id: model.name(),
+            model_sha: model.fingerprint()?,
+            model_dtype: "float32",
+            model_type: TeiModelType::Embedding(TeiEmbeddingModelType {
+                pooling: pooling_name(&model)?,
+            }),
+            max_input_length: model.max_input_length()?,
+            version: VERSION,
+        })
+    })
+    .await
+    .map(Json)
+    .map_err(TeiError::from)
+}
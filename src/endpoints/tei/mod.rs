@@ -0,0 +1,159 @@
+//! Endpoints compatible with [Text Embeddings Inference].
+//!
+//! Text Embeddings Inference serves a single model per server, so each enabled model is
+//! served under its own prefix, e.g. `/tei/sentence-transformers/all-MiniLM-L6-v2/embed`.
+//! Existing clients only need their base URL pointed at the prefix of the desired model.
+//!
+//! [Text Embeddings Inference]: https://github.com/huggingface/text-embeddings-inference
+
+use embedder_err::{EmbedderAPIError, EmbedderError};
+use embedder_external::axum::{
+    http::{HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use embedder_external::serde::{Deserialize, Serialize};
+
+use super::EmbeddingModel;
+
+mod embed;
+pub use embed::*;
+
+mod info;
+pub use info::*;
+
+mod tokenize;
+pub use tokenize::*;
+
+/// Build the router serving all the enabled models under their own prefixes.
+pub fn router() -> Router {
+    EmbeddingModel::all()
+        .into_iter()
+        .fold(Router::new(), |router, model| {
+            router.nest(
+                &format!("/{name}", name = model.name()),
+                Router::new()
+                    .route("/embed", post(embed))
+                    .route("/embed_all", post(embed_all))
+                    .route("/tokenize", post(tokenize))
+                    .route("/info", get(info))
+                    .with_state(model),
+            )
+        })
+}
+
+/// The `inputs` of a request, which can either be a single string or a batch of them.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Input {
+    Single(String),
+    Batch(Vec<String>),
+}
+
+impl Input {
+    /// Convert the input into a list of documents, rejecting empty batches.
+    pub fn into_documents(self) -> Result<Vec<String>, EmbedderAPIError> {
+        match self {
+            Self::Single(document) => Ok(vec![document]),
+            Self::Batch(documents) if documents.is_empty() => Err(EmbedderAPIError::EmbedderError(
+                EmbedderError::EmptyInputError,
+            )),
+            Self::Batch(documents) => Ok(documents),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// The response header carrying the fingerprint of the model files, since the bodies of Text
+/// Embeddings Inference have no room for it.
+pub const FINGERPRINT_HEADER: &str = "x-model-fingerprint";
+
+/// Respond with the body as JSON, along with the fingerprint of the model if it has one.
+fn with_fingerprint<T: Serialize>(body: T, fingerprint: Option<String>) -> Response {
+    let mut response = Json(body).into_response();
+    if let Some(fingerprint) = fingerprint.and_then(|value| HeaderValue::from_str(&value).ok()) {
+        response
+            .headers_mut()
+            .insert(FINGERPRINT_HEADER, fingerprint);
+    }
+
+    response
+}
+
+/// Reject any documents that would be truncated by the tokenizer of the model.
+///
+/// Text Embeddings Inference only truncates the inputs when `truncate` is set.
+fn validate_length(model: &EmbeddingModel, documents: &[String]) -> Result<(), EmbedderAPIError> {
+    let max_input_length = model.max_input_length()?;
+    let errors = model
+        .tokenize(documents, true)?
+        .iter()
+        .enumerate()
+        .filter(|(_, document)| document.truncated)
+        .map(|(idx, _)| {
+            (
+                idx,
+                match max_input_length {
+                    Some(length) => format!("`inputs` must have less than {length} tokens."),
+                    None => "`inputs` is too long.".to_owned(),
+                },
+                "truncated".to_owned(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(EmbedderAPIError::CannotEmbedInput(errors))
+    }
+}
+
+/// The error body returned by Text Embeddings Inference.
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    error: String,
+    error_type: &'static str,
+}
+
+/// Wrapper around [`EmbedderAPIError`] to respond in the Text Embeddings Inference format.
+#[derive(Debug)]
+pub struct TeiError(EmbedderAPIError);
+
+impl From<EmbedderAPIError> for TeiError {
+    fn from(error: EmbedderAPIError) -> Self {
+        Self(error)
+    }
+}
+
+impl IntoResponse for TeiError {
+    fn into_response(self) -> Response {
+        let (status, error_type) = match &self.0 {
+            EmbedderAPIError::CannotEmbedInput(_) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, "validation")
+            }
+            EmbedderAPIError::EmbedderError(EmbedderError::EmptyInputError) => {
+                (StatusCode::BAD_REQUEST, "empty")
+            }
+            EmbedderAPIError::EmbedderError(EmbedderError::TokenizerError(_)) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, "tokenizer")
+            }
+            error => (error.status_code(), "backend"),
+        };
+
+        let error = match &self.0 {
+            EmbedderAPIError::CannotEmbedInput(inputs) => inputs
+                .iter()
+                .map(|(idx, message, _)| format!("inputs.{idx}: {message}"))
+                .collect::<Vec<_>>()
+                .join("; "),
+            error => error.to_string(),
+        };
+
+        (status, Json(ErrorResponse { error, error_type })).into_response()
+    }
+}
@@ -0,0 +1,69 @@
+//! The `tokenize` endpoint of Text Embeddings Inference.
+
+use embedder_external::axum::extract::{Json, State};
+use embedder_external::serde::{Deserialize, Serialize};
+
+use super::{default_true, EmbeddingModel, Input, TeiError};
+use crate::common::spawn_blocking;
+
+#[derive(Debug, Deserialize)]
+pub struct TeiTokenizeRequest {
+    inputs: Input,
+    #[serde(default = "default_true")]
+    add_special_tokens: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TeiToken {
+    id: u32,
+    text: String,
+    special: bool,
+    start: Option<usize>,
+    stop: Option<usize>,
+}
+
+/// Tokenize the `inputs` with the tokenizer of the model.
+pub async fn tokenize(
+    State(model): State<EmbeddingModel>,
+    Json(request): Json<TeiTokenizeRequest>,
+) -> Result<Json<Vec<Vec<TeiToken>>>, TeiError> {
+    let documents = request.inputs.into_documents()?;
+
+    spawn_blocking(move || {
+        Ok(model
+            .tokenize(&documents, request.add_special_tokens)?
+            .into_iter()
+            .zip(documents.iter())
+            .map(|(tokenized, document)| {
+                tokenized
+                    .tokens
+                    .into_iter()
+                    .map(|token| {
+                        let (start, stop) = token.offsets;
+                        if token.special {
+                            // Special tokens do not correspond to any part of the input.
+                            TeiToken {
+                                id: token.id,
+                                text: token.token,
+                                special: true,
+                                start: None,
+                                stop: None,
+                            }
+                        } else {
+                            TeiToken {
+                                id: token.id,
+                                text: document.chars().skip(start).take(stop - start).collect(),
+                                special: false,
+                                start: Some(start),
+                                stop: Some(stop),
+                            }
+                        }
+                    })
+                    .collect()
+            })
+            .collect())
+    })
+    .await
+    .map(Json)
+    .map_err(TeiError::from)
+}
@@ -0,0 +1,22 @@
+//! Helpers for the tests of the endpoints.
+
+use embedder_lib::transform::models::mock::{self, MockOptions};
+
+use super::EmbeddingModel;
+
+/// The marker that fails any request to the [`mock_model`] with a document containing it.
+pub const MOCK_FAIL_ON: &str = "__fail__";
+
+/// The `mock` model, configured to fail on [`MOCK_FAIL_ON`].
+///
+/// The mock is a singleton created once per process, so every test using it must go through
+/// this function to share the same options.
+pub fn mock_model() -> EmbeddingModel {
+    mock::Model::init(MockOptions {
+        fail_on: Some(MOCK_FAIL_ON.to_owned()),
+        ..Default::default()
+    })
+    .expect("Failed to create the mock model.");
+
+    EmbeddingModel::Mock
+}
@@ -0,0 +1,169 @@
+//! The `tokenize` and `detokenize` endpoints, exposing the tokenizer of each model.
+
+use embedder_err::EmbedderAPIError;
+use embedder_external::axum::Json;
+use embedder_external::serde::{Deserialize, Serialize};
+use embedder_lib::transform::{self, TokenizedDocument};
+
+use super::{EmbeddingModel, InputType};
+use crate::common::spawn_blocking;
+
+#[derive(Debug, Deserialize)]
+pub struct TokenizeRequest {
+    model: EmbeddingModel,
+    documents: Vec<String>,
+    /// Apply the prefix the model expects for this type of input, as `/embed` would.
+    #[serde(default)]
+    input_type: Option<InputType>,
+}
+
+/// The tokens of a document, as they are fed to the model by `/embed`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentTokens {
+    ids: Vec<u32>,
+    tokens: Vec<String>,
+    /// The character offsets in the document; special and prompt tokens do not have any.
+    offsets: Vec<Option<(usize, usize)>>,
+    /// `1` for the tokens added by the tokenizer, e.g. `[CLS]` or `[SEP]`, otherwise `0`.
+    special_tokens_mask: Vec<u8>,
+    /// The number of tokens of the prompt, which count towards the `maxInputLength`.
+    prompt_tokens: usize,
+    /// The total number of tokens, including the special and prompt tokens.
+    count: usize,
+    /// Whether the document was truncated to the `maxInputLength` of the model.
+    truncated: bool,
+}
+
+impl From<TokenizedDocument> for DocumentTokens {
+    fn from(document: TokenizedDocument) -> Self {
+        let count = document.len();
+
+        Self {
+            ids: document.tokens.iter().map(|token| token.id).collect(),
+            offsets: document
+                .tokens
+                .iter()
+                .map(|token| (!token.special && !token.prompt).then_some(token.offsets))
+                .collect(),
+            special_tokens_mask: document
+                .tokens
+                .iter()
+                .map(|token| token.special as u8)
+                .collect(),
+            prompt_tokens: document.tokens.iter().filter(|token| token.prompt).count(),
+            tokens: document
+                .tokens
+                .into_iter()
+                .map(|token| token.token)
+                .collect(),
+            count,
+            truncated: document.truncated,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenizeResponse {
+    model: EmbeddingModel,
+    max_input_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    input_type: Option<InputType>,
+    /// The prefix prepended to each of the documents, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt: Option<&'static str>,
+    documents: Vec<DocumentTokens>,
+}
+
+/// Tokenize the `documents` with the tokenizer of the model, exactly as `/embed` would.
+pub async fn tokenize(
+    Json(request): Json<TokenizeRequest>,
+) -> Result<Json<TokenizeResponse>, EmbedderAPIError> {
+    let model = request.model.clone();
+    let input_type = request.input_type;
+
+    spawn_blocking(move || {
+        let prompts = request.model.prompts()?;
+        let prompt = request
+            .input_type
+            .and_then(|input_type| prompts.prompt(input_type.into()));
+        let documents = request.model.tokenize_with_options(
+            &request.documents,
+            request.input_type.map(transform::InputType::from),
+        )?;
+
+        Ok(TokenizeResponse {
+            model,
+            max_input_length: request.model.max_input_length()?,
+            input_type,
+            prompt,
+            documents: documents.into_iter().map(DocumentTokens::from).collect(),
+        })
+    })
+    .await
+    .map(Json)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DetokenizeRequest {
+    model: EmbeddingModel,
+    /// The token ids of each document.
+    ids: Vec<Vec<u32>>,
+    #[serde(default)]
+    skip_special_tokens: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DetokenizeResponse {
+    model: EmbeddingModel,
+    documents: Vec<String>,
+}
+
+/// Decode the token `ids` back into text with the tokenizer of the model.
+pub async fn detokenize(
+    Json(request): Json<DetokenizeRequest>,
+) -> Result<Json<DetokenizeResponse>, EmbedderAPIError> {
+    let model = request.model.clone();
+
+    spawn_blocking(move || {
+        Ok(DetokenizeResponse {
+            model,
+            documents: request
+                .model
+                .detokenize(&request.ids, request.skip_special_tokens)?,
+        })
+    })
+    .await
+    .map(Json)
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::endpoints::test_utils::mock_model;
+
+    #[tokio::test]
+    async fn tokenize_mock_model() {
+        let Json(response) = tokenize(Json(TokenizeRequest {
+            model: mock_model(),
+            documents: vec!["foo bar".to_owned(), "baz".to_owned()],
+            input_type: None,
+        }))
+        .await
+        .expect("Failed to tokenize the documents.");
+
+        assert_eq!(response.max_input_length, Some(512));
+        assert_eq!(response.prompt, None);
+
+        let foo_bar = &response.documents[0];
+        assert_eq!(foo_bar.tokens, ["[CLS]", "[UNK]", "[UNK]", "[SEP]"]);
+        assert_eq!(foo_bar.ids, [2, 1, 1, 3]);
+        assert_eq!(foo_bar.offsets, [None, Some((0, 3)), Some((4, 7)), None]);
+        assert_eq!(foo_bar.special_tokens_mask, [1, 0, 0, 1]);
+        assert_eq!(foo_bar.count, 4);
+        assert!(!foo_bar.truncated);
+
+        assert_eq!(response.documents[1].count, 3);
+    }
+}
@@ -0,0 +1,154 @@
+//! The gRPC server, running alongside the HTTP API.
+//!
+//! This shares the same model singletons and error mapping as the HTTP endpoints; see
+//! `proto/embedder.proto` for the service definition.
+
+use embedder_err::EmbedderAPIError;
+use embedder_external::ndarray;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+use crate::common::{calculate_default_batch_size, spawn_blocking};
+use crate::endpoints::{EmbeddingModel, ModelDescription};
+
+/// Generated code from `proto/embedder.proto`.
+pub mod proto {
+    tonic::include_proto!("embedder.v1");
+}
+
+use proto::embedder_server::{Embedder, EmbedderServer};
+
+/// Convert the rows of an array into protobuf embeddings.
+fn to_proto_embeddings(array: ndarray::Array2<f32>) -> Vec<proto::Embedding> {
+    array
+        .rows()
+        .into_iter()
+        .map(|row| proto::Embedding {
+            values: row.to_vec(),
+        })
+        .collect()
+}
+
+/// Resolve the model and batch size of a request.
+fn parse_request(
+    request: &proto::EmbedRequest,
+) -> Result<(EmbeddingModel, usize), EmbedderAPIError> {
+    let model = EmbeddingModel::from_name(&request.model)?;
+    let batch_size = request
+        .batch_size
+        .map(|batch_size| batch_size as usize)
+        .filter(|batch_size| *batch_size > 0)
+        .unwrap_or_else(|| calculate_default_batch_size(request.documents.len()));
+
+    Ok((model, batch_size))
+}
+
+#[derive(Debug, Default)]
+pub struct EmbedderService;
+
+#[tonic::async_trait]
+impl Embedder for EmbedderService {
+    async fn embed(
+        &self,
+        request: Request<proto::EmbedRequest>,
+    ) -> Result<Response<proto::EmbedResponse>, Status> {
+        let start = Instant::now();
+        let request = request.into_inner();
+        let (model, batch_size) = parse_request(&request)?;
+
+        let (embeddings, fingerprint) = spawn_blocking(move || {
+            model.ensure_dense()?;
+            let fingerprint = model.fingerprint()?;
+            model
+                .embed_to_array(request.documents, Some(batch_size))
+                .map(|embeddings| (embeddings, fingerprint))
+        })
+        .await?;
+
+        Ok(Response::new(proto::EmbedResponse {
+            model: request.model,
+            duration: start.elapsed().as_secs_f32(),
+            embeddings: to_proto_embeddings(embeddings),
+            fingerprint,
+        }))
+    }
+
+    type EmbedStreamStream = ReceiverStream<Result<proto::EmbedStreamResponse, Status>>;
+
+    async fn embed_stream(
+        &self,
+        request: Request<proto::EmbedRequest>,
+    ) -> Result<Response<Self::EmbedStreamStream>, Status> {
+        let request = request.into_inner();
+        let (model, batch_size) = parse_request(&request)?;
+        // Reject the request upfront rather than in the stream.
+        let (model, fingerprint) = spawn_blocking(move || {
+            model.ensure_dense()?;
+            let fingerprint = model.fingerprint()?;
+            Ok((model, fingerprint))
+        })
+        .await?;
+        let (sender, receiver) = mpsc::channel(1);
+
+        tokio::spawn(async move {
+            for (id, batch) in request.documents.chunks(batch_size).enumerate() {
+                let batch = batch.to_vec();
+                let batch_model = model.clone();
+                let response =
+                    spawn_blocking(move || batch_model.embed_to_array(batch, Some(batch_size)))
+                        .await
+                        .map(|embeddings| proto::EmbedStreamResponse {
+                            model: request.model.clone(),
+                            offset: (id * batch_size) as u32,
+                            embeddings: to_proto_embeddings(embeddings),
+                            fingerprint: fingerprint.clone(),
+                        })
+                        .map_err(Status::from);
+
+                let is_err = response.is_err();
+                // Stop if the client has disconnected or an error has been sent.
+                if sender.send(response).await.is_err() || is_err {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn list_models(
+        &self,
+        _request: Request<proto::ListModelsRequest>,
+    ) -> Result<Response<proto::ListModelsResponse>, Status> {
+        let models = spawn_blocking(ModelDescription::all).await?;
+
+        Ok(Response::new(proto::ListModelsResponse {
+            models: models
+                .into_iter()
+                .map(|description| proto::ModelDescription {
+                    name: description.name.to_owned(),
+                    pooling: description.pooling.to_owned(),
+                    max_input_length: description.max_input_length.map(|length| length as u32),
+                    fingerprint: description.fingerprint,
+                })
+                .collect(),
+        }))
+    }
+}
+
+/// Serve the gRPC API, together with the standard health-check service.
+pub async fn serve(socket_addr: std::net::SocketAddr) -> Result<(), EmbedderAPIError> {
+    let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
+    health_reporter
+        .set_serving::<EmbedderServer<EmbedderService>>()
+        .await;
+
+    tonic::transport::Server::builder()
+        .add_service(health_service)
+        .add_service(EmbedderServer::new(EmbedderService))
+        .serve(socket_addr)
+        .await
+        .map_err(EmbedderAPIError::GrpcError)
+}
@@ -0,0 +1,821 @@
+//! The `inspect-model` command, describing a model folder before it is added to `models.toml`.
+//!
+//! The ONNX graph and the tokenizer are read without creating a session, so they are described
+//! even if the model cannot be loaded yet; the sample embedding needs the files to match the
+//! `SHA256SUMS` of the folder, as `custom::Model::from_path` does. Each file is read once, then
+//! hashed, parsed and handed over to the model from memory.
+
+use embedder_err::{EmbedderAPIError, EmbedderError};
+use embedder_external::serde::Serialize;
+use embedder_external::{fastembed, serde_json, tokenizers};
+use embedder_lib::integrity;
+use embedder_lib::transform::{
+    models::custom, CanTransform, ModelKind, OnnxDimension, OnnxGraph, OnnxTensor,
+};
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::args::InspectModelArgs;
+
+/// The files of a model folder besides the ONNX model; the optional ones may be missing.
+const MODEL_FILES: [(&str, bool); 4] = [
+    ("tokenizer.json", true),
+    ("config.json", false),
+    ("special_tokens_map.json", false),
+    ("tokenizer_config.json", true),
+];
+
+/// A file of the model folder.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDescription {
+    pub name: String,
+    pub size: usize,
+    pub sha256: String,
+    /// Whether the file matches the `SHA256SUMS` of the folder; `None` if it is not listed.
+    pub verified: Option<bool>,
+}
+
+/// An input or output of the ONNX graph.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TensorDescription {
+    pub name: String,
+    pub element_type: &'static str,
+    /// The fixed sizes as numbers, and the variable ones by their name or as `null`.
+    pub shape: Option<Vec<serde_json::Value>>,
+}
+
+impl From<&OnnxTensor> for TensorDescription {
+    fn from(tensor: &OnnxTensor) -> Self {
+        Self {
+            name: tensor.name.clone(),
+            element_type: tensor.element_type,
+            shape: tensor.shape.as_ref().map(|shape| {
+                shape
+                    .iter()
+                    .map(|dimension| match dimension {
+                        OnnxDimension::Fixed(size) => serde_json::Value::from(*size),
+                        OnnxDimension::Symbolic(name) => serde_json::Value::from(name.as_str()),
+                        OnnxDimension::Unknown => serde_json::Value::Null,
+                    })
+                    .collect()
+            }),
+        }
+    }
+}
+
+/// The ONNX graph, and the output the model would be loaded with.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OnnxDescription {
+    pub ir_version: i64,
+    pub producer: String,
+    /// The version of each operator set, by domain; `ai.onnx` for the default domain.
+    pub opsets: BTreeMap<String, i64>,
+    pub inputs: Vec<TensorDescription>,
+    pub outputs: Vec<TensorDescription>,
+    pub output_key: Option<String>,
+    pub output_key_source: Option<&'static str>,
+    pub dimension: Option<usize>,
+    /// Why no output could be chosen, if none was.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_error: Option<String>,
+}
+
+/// A special token of the tokenizer.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpecialToken {
+    /// The role of the token in `special_tokens_map.json`, e.g. `cls_token`.
+    pub role: Option<String>,
+    pub content: String,
+    pub id: Option<u32>,
+}
+
+/// The tokenizer of the model.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenizerDescription {
+    /// The type of the tokenization model, e.g. `WordPiece` or `BPE`.
+    pub model_type: Option<String>,
+    pub vocab_size: usize,
+    /// The `model_max_length` of `tokenizer_config.json`.
+    pub model_max_length: Option<u64>,
+    /// The truncation length of `tokenizer.json`, if any.
+    pub truncation: Option<usize>,
+    pub special_tokens: Vec<SpecialToken>,
+}
+
+/// The embedding of the sample document, without normalization.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SampleDescription {
+    pub text: String,
+    pub tokens: usize,
+    pub dimension: usize,
+    pub norm: f32,
+    pub max_input_length: Option<usize>,
+    pub fingerprint: Option<String>,
+}
+
+/// The description of a model folder.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInspection {
+    pub path: String,
+    pub files: Vec<FileDescription>,
+    pub onnx: OnnxDescription,
+    pub tokenizer: TokenizerDescription,
+    pub sample: Option<SampleDescription>,
+    /// Why the model could not be loaded to embed the sample, if it could not.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_error: Option<String>,
+}
+
+/// Read a file of the model folder; `None` if an optional file is missing.
+fn read_file(path: &Path, name: &str, required: bool) -> Result<Option<Vec<u8>>, EmbedderAPIError> {
+    match std::fs::read(path.join(name)) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(err) if !required && err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(EmbedderAPIError::IoError(std::io::Error::new(
+            err.kind(),
+            format!("{path:?}: {err}", path = path.join(name)),
+        ))),
+    }
+}
+
+/// Parse a JSON file of the model folder.
+fn parse_json(name: &str, bytes: &[u8]) -> Result<serde_json::Value, EmbedderError> {
+    serde_json::from_slice(bytes).map_err(|err| EmbedderError::ModelLoadError {
+        name: name.to_owned(),
+        error: format!("not valid JSON: {err}"),
+    })
+}
+
+/// Describe the files read from the folder, checking them against its `SHA256SUMS`.
+fn describe_files(
+    files: &[(&str, Vec<u8>)],
+    manifest: &HashMap<String, String>,
+) -> Vec<FileDescription> {
+    files
+        .iter()
+        .map(|(name, bytes)| {
+            let sha256 = integrity::sha256_hex(bytes);
+
+            FileDescription {
+                name: (*name).to_owned(),
+                size: bytes.len(),
+                verified: manifest.get(*name).map(|digest| *digest == sha256),
+                sha256,
+            }
+        })
+        .collect()
+}
+
+/// The fingerprint of the files, which must all match the `SHA256SUMS` of the folder, as
+/// `custom::Model::from_path` requires.
+fn verified_fingerprint(path: &Path, files: &[FileDescription]) -> Result<String, EmbedderError> {
+    files
+        .iter()
+        .map(|file| match file.verified {
+            Some(true) => Ok(file.sha256.as_str()),
+            verified => Err(EmbedderError::ModelIntegrityError {
+                path: path.join(&file.name).into(),
+                error: match verified {
+                    Some(_) => format!("does not match {}", integrity::MANIFEST_FILE),
+                    None => format!("not listed in {}", integrity::MANIFEST_FILE),
+                },
+            }),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(integrity::fingerprint)
+}
+
+/// Describe the ONNX graph, and the output chosen for the embeddings.
+fn describe_onnx(graph: &OnnxGraph, output_key: Option<&str>) -> OnnxDescription {
+    let selection = graph.select_output(ModelKind::Dense, output_key);
+
+    OnnxDescription {
+        ir_version: graph.ir_version,
+        producer: graph.producer.clone(),
+        opsets: graph
+            .opsets
+            .iter()
+            .map(|opset| match opset.domain.as_str() {
+                "" => ("ai.onnx".to_owned(), opset.version),
+                domain => (domain.to_owned(), opset.version),
+            })
+            .collect(),
+        inputs: graph.inputs.iter().map(TensorDescription::from).collect(),
+        outputs: graph.outputs.iter().map(TensorDescription::from).collect(),
+        output_key: selection.as_ref().ok().map(|output| output.key.clone()),
+        output_key_source: selection.as_ref().ok().map(|output| output.source.name()),
+        dimension: selection.as_ref().ok().and_then(|output| output.dimension),
+        output_error: selection.err(),
+    }
+}
+
+/// Describe the tokenizer from its files.
+fn describe_tokenizer(
+    tokenizer_file: &[u8],
+    special_tokens_map_file: Option<&[u8]>,
+    tokenizer_config_file: &[u8],
+) -> Result<TokenizerDescription, EmbedderError> {
+    let tokenizer = tokenizers::Tokenizer::from_bytes(tokenizer_file).map_err(|err| {
+        EmbedderError::ModelLoadError {
+            name: "tokenizer.json".to_owned(),
+            error: err.to_string(),
+        }
+    })?;
+    let tokenizer_json = parse_json("tokenizer.json", tokenizer_file)?;
+    let tokenizer_config = parse_json("tokenizer_config.json", tokenizer_config_file)?;
+    let special_tokens_map = special_tokens_map_file
+        .map(|bytes| parse_json("special_tokens_map.json", bytes))
+        .transpose()?;
+
+    // The tokens are either strings, or objects with their `content`.
+    let content = |token: &serde_json::Value| {
+        token
+            .as_str()
+            .or_else(|| token.get("content").and_then(serde_json::Value::as_str))
+            .map(ToOwned::to_owned)
+    };
+
+    let mut special_tokens = special_tokens_map
+        .as_ref()
+        .and_then(serde_json::Value::as_object)
+        .into_iter()
+        .flatten()
+        .flat_map(|(role, tokens)| match tokens {
+            serde_json::Value::Array(tokens) => tokens
+                .iter()
+                .filter_map(|token| Some((role.clone(), content(token)?)))
+                .collect::<Vec<_>>(),
+            token => content(token)
+                .map(|content| (role.clone(), content))
+                .into_iter()
+                .collect(),
+        })
+        .map(|(role, content)| SpecialToken {
+            role: Some(role),
+            id: tokenizer.token_to_id(&content),
+            content,
+        })
+        .collect::<Vec<_>>();
+
+    // The special tokens added to the tokenizer but missing from the map.
+    tokenizer
+        .get_added_tokens_decoder()
+        .into_iter()
+        .filter(|(_, token)| token.special)
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .for_each(|(id, token)| {
+            if !special_tokens
+                .iter()
+                .any(|special| special.content == token.content)
+            {
+                special_tokens.push(SpecialToken {
+                    role: None,
+                    content: token.content,
+                    id: Some(id),
+                });
+            }
+        });
+
+    Ok(TokenizerDescription {
+        model_type: tokenizer_json
+            .pointer("/model/type")
+            .and_then(serde_json::Value::as_str)
+            .map(ToOwned::to_owned),
+        vocab_size: tokenizer.get_vocab_size(true),
+        model_max_length: tokenizer_config
+            .get("model_max_length")
+            .and_then(serde_json::Value::as_u64),
+        truncation: tokenizer
+            .get_truncation()
+            .map(|truncation| truncation.max_length),
+        special_tokens,
+    })
+}
+
+/// Load the model from the files already read, and embed the sample document.
+fn embed_sample(
+    args: &InspectModelArgs,
+    files: &[FileDescription],
+    contents: Vec<(&str, Vec<u8>)>,
+) -> Result<SampleDescription, EmbedderError> {
+    let fingerprint = verified_fingerprint(args.path(), files)?;
+
+    // The required files were all read, and a missing JSON file is the same as an empty object
+    // to `fastembed`.
+    let mut contents = contents.into_iter().collect::<HashMap<_, _>>();
+    let mut take = |name: &str| contents.remove(name).unwrap_or_else(|| b"{}".to_vec());
+    let onnx_file = take(args.model_file());
+    let tokenizer_files = fastembed::TokenizerFiles {
+        tokenizer_file: take("tokenizer.json"),
+        config_file: take("config.json"),
+        special_tokens_map_file: take("special_tokens_map.json"),
+        tokenizer_config_file: take("tokenizer_config.json"),
+    };
+
+    let builder = custom::Model::builder(args.path().display().to_string()).pooling(args.pooling());
+    let builder = match args.output_key() {
+        Some(output_key) => builder.output_key(output_key),
+        None => builder,
+    };
+    let model = builder.load_from_memory(onnx_file, tokenizer_files)?;
+
+    let tokens = model
+        .tokenize(&[args.sample()], true)?
+        .first()
+        .map_or(0, |document| document.len());
+    let embeddings = model.embed_to_pooled_array(vec![args.sample()], None)?;
+    let embedding = embeddings.row(0);
+
+    Ok(SampleDescription {
+        text: args.sample().to_owned(),
+        tokens,
+        dimension: embedding.len(),
+        norm: embedding.dot(&embedding).sqrt(),
+        max_input_length: model.max_input_length(),
+        fingerprint: Some(fingerprint),
+    })
+}
+
+/// Describe the model folder of `inspect-model`.
+pub fn inspect_model(args: &InspectModelArgs) -> Result<ModelInspection, EmbedderAPIError> {
+    let path = args.path();
+    let manifest = std::fs::read_to_string(path.join(integrity::MANIFEST_FILE))
+        .map(|contents| integrity::parse_manifest(&contents))
+        .unwrap_or_default();
+
+    let contents = std::iter::once((args.model_file(), true))
+        .chain(MODEL_FILES)
+        .map(|(name, required)| {
+            read_file(path, name, required).map(|bytes| bytes.map(|bytes| (name, bytes)))
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    let file = |name: &str| {
+        contents
+            .iter()
+            .find(|(file_name, _)| *file_name == name)
+            .map(|(_, bytes)| bytes.as_slice())
+    };
+
+    // The required files were all read above.
+    let files = describe_files(&contents, &manifest);
+    let onnx = describe_onnx(
+        &OnnxGraph::parse(file(args.model_file()).unwrap_or_default())?,
+        args.output_key(),
+    );
+    let tokenizer = describe_tokenizer(
+        file("tokenizer.json").unwrap_or_default(),
+        file("special_tokens_map.json"),
+        file("tokenizer_config.json").unwrap_or_default(),
+    )?;
+
+    let (sample, sample_error) = match embed_sample(args, &files, contents) {
+        Ok(sample) => (Some(sample), None),
+        Err(err) => (None, Some(err.to_string())),
+    };
+
+    Ok(ModelInspection {
+        path: path.display().to_string(),
+        files,
+        onnx,
+        tokenizer,
+        sample,
+        sample_error,
+    })
+}
+
+/// Write the description for reading in a terminal.
+fn write_text(inspection: &ModelInspection, out: &mut impl std::fmt::Write) -> std::fmt::Result {
+    let shape = |tensor: &TensorDescription| match &tensor.shape {
+        Some(shape) => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|dimension| match dimension {
+                    serde_json::Value::String(name) => name.clone(),
+                    serde_json::Value::Null => "?".to_owned(),
+                    dimension => dimension.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        None => "(unknown shape)".to_owned(),
+    };
+    let onnx = &inspection.onnx;
+    let tokenizer = &inspection.tokenizer;
+
+    writeln!(out, "{}", inspection.path)?;
+
+    writeln!(out, "\nFiles:")?;
+    for file in &inspection.files {
+        let verified = match file.verified {
+            Some(true) => "verified",
+            Some(false) => "MISMATCH",
+            None => "not in SHA256SUMS",
+        };
+        writeln!(
+            out,
+            "  {name:<28} {size:>12} bytes  {sha256}  {verified}",
+            name = file.name,
+            size = file.size,
+            sha256 = file.sha256,
+        )?;
+    }
+
+    writeln!(
+        out,
+        "\nONNX: IR version {ir_version}, produced by {producer}, opsets {opsets}",
+        ir_version = onnx.ir_version,
+        producer = match onnx.producer.as_str() {
+            "" => "an unknown exporter",
+            producer => producer,
+        },
+        opsets = onnx
+            .opsets
+            .iter()
+            .map(|(domain, version)| format!("{domain} {version}"))
+            .collect::<Vec<_>>()
+            .join(", "),
+    )?;
+    for (direction, tensors) in [("Input", &onnx.inputs), ("Output", &onnx.outputs)] {
+        for tensor in tensors {
+            writeln!(
+                out,
+                "  {direction:<6} {name:<28} {element_type:<8} {shape}",
+                name = tensor.name,
+                element_type = tensor.element_type,
+                shape = shape(tensor),
+            )?;
+        }
+    }
+    match (&onnx.output_key, &onnx.output_error) {
+        (Some(output_key), _) => writeln!(
+            out,
+            "  Embeddings from '{output_key}' ({source}), dimension {dimension}",
+            source = onnx.output_key_source.unwrap_or("configured"),
+            dimension = onnx
+                .dimension
+                .map_or_else(|| "unknown".to_owned(), |dimension| dimension.to_string()),
+        )?,
+        (None, Some(error)) => writeln!(out, "  No output for the embeddings: {error}")?,
+        (None, None) => {}
+    }
+
+    writeln!(
+        out,
+        "\nTokenizer: {model_type}, {vocab_size} tokens, model_max_length {max_length}, truncation {truncation}",
+        model_type = tokenizer.model_type.as_deref().unwrap_or("unknown model"),
+        vocab_size = tokenizer.vocab_size,
+        max_length = tokenizer
+            .model_max_length
+            .map_or_else(|| "unset".to_owned(), |length| length.to_string()),
+        truncation = tokenizer
+            .truncation
+            .map_or_else(|| "none".to_owned(), |length| length.to_string()),
+    )?;
+    for token in &tokenizer.special_tokens {
+        writeln!(
+            out,
+            "  {role:<28} {content:<16} {id}",
+            role = token.role.as_deref().unwrap_or("(added token)"),
+            content = token.content,
+            id = token
+                .id
+                .map_or_else(|| "not in the vocabulary".to_owned(), |id| id.to_string()),
+        )?;
+    }
+
+    match (&inspection.sample, &inspection.sample_error) {
+        (Some(sample), _) => writeln!(
+            out,
+            "\nSample {text:?}: {tokens} tokens, dimension {dimension}, norm {norm:.6}, max input length {max_length}, fingerprint {fingerprint}",
+            text = sample.text,
+            tokens = sample.tokens,
+            dimension = sample.dimension,
+            norm = sample.norm,
+            max_length = sample
+                .max_input_length
+                .map_or_else(|| "unknown".to_owned(), |length| length.to_string()),
+            fingerprint = sample.fingerprint.as_deref().unwrap_or("none"),
+        ),
+        (None, Some(error)) => writeln!(out, "\nSample: the model could not be loaded: {error}"),
+        (None, None) => Ok(()),
+    }
+}
+
+/// Run `inspect-model`, printing the description to stdout.
+pub fn run(args: &InspectModelArgs) -> Result<(), EmbedderAPIError> {
+    let inspection = inspect_model(args)?;
+
+    let output = if args.json() {
+        serde_json::to_string_pretty(&inspection)?
+    } else {
+        let mut output = String::new();
+        // Writing to a `String` cannot fail.
+        let _ = write_text(&inspection, &mut output);
+        output
+    };
+    println!("{}", output.trim_end());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedder_lib::transform::OnnxOpset;
+
+    /// A `WordLevel` tokenizer with `[CLS]` and `[SEP]` in its vocabulary, and `<extra>` added
+    /// as a special token.
+    fn tokenizer_file() -> Vec<u8> {
+        let model = tokenizers::models::wordlevel::WordLevel::builder()
+            .vocab(
+                [("[UNK]", 0), ("[CLS]", 1), ("[SEP]", 2), ("hello", 3)]
+                    .into_iter()
+                    .map(|(token, id)| (token.to_owned(), id))
+                    .collect(),
+            )
+            .unk_token("[UNK]".to_owned())
+            .build()
+            .expect("Failed to build the tokenizer model.");
+        let mut tokenizer = tokenizers::Tokenizer::new(model);
+        tokenizer.add_special_tokens(&[
+            tokenizers::AddedToken::from("[CLS]", true),
+            tokenizers::AddedToken::from("[SEP]", true),
+            tokenizers::AddedToken::from("<extra>", true),
+        ]);
+
+        tokenizer
+            .to_string(false)
+            .expect("Failed to serialize the tokenizer.")
+            .into_bytes()
+    }
+
+    #[test]
+    fn describe_special_tokens() {
+        let special_tokens_map = serde_json::json!({
+            "cls_token": "[CLS]",
+            "sep_token": {"content": "[SEP]", "lstrip": false, "rstrip": false},
+            "unk_token": "[UNK]",
+            "additional_special_tokens": ["[MISSING]"],
+        })
+        .to_string();
+
+        let description = describe_tokenizer(
+            &tokenizer_file(),
+            Some(special_tokens_map.as_bytes()),
+            br#"{"model_max_length": 128}"#,
+        )
+        .expect("Failed to describe the tokenizer.");
+
+        assert_eq!(description.model_type.as_deref(), Some("WordLevel"));
+        assert_eq!(description.vocab_size, 5);
+        assert_eq!(description.model_max_length, Some(128));
+        assert_eq!(description.truncation, None);
+
+        let token = |role: Option<&str>| {
+            description
+                .special_tokens
+                .iter()
+                .filter(|token| token.role.as_deref() == role)
+                .map(|token| (token.content.as_str(), token.id))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(token(Some("cls_token")), [("[CLS]", Some(1))]);
+        assert_eq!(token(Some("sep_token")), [("[SEP]", Some(2))]);
+        assert_eq!(token(Some("unk_token")), [("[UNK]", Some(0))]);
+        assert_eq!(
+            token(Some("additional_special_tokens")),
+            [("[MISSING]", None)]
+        );
+        // Only the added special token missing from the map is listed on its own.
+        assert_eq!(token(None), [("<extra>", Some(4))]);
+        assert_eq!(description.special_tokens.len(), 5);
+    }
+
+    #[test]
+    fn describe_without_special_tokens_map() {
+        let description = describe_tokenizer(&tokenizer_file(), None, b"{}")
+            .expect("Failed to describe the tokenizer.");
+
+        assert_eq!(description.model_max_length, None);
+        assert_eq!(
+            description
+                .special_tokens
+                .iter()
+                .map(|token| (token.role.as_deref(), token.content.as_str()))
+                .collect::<Vec<_>>(),
+            [(None, "[CLS]"), (None, "[SEP]"), (None, "<extra>")]
+        );
+
+        assert!(matches!(
+            describe_tokenizer(b"not json", None, b"{}"),
+            Err(EmbedderError::ModelLoadError { .. })
+        ));
+        assert!(matches!(
+            describe_tokenizer(&tokenizer_file(), Some(b"{"), b"{}"),
+            Err(EmbedderError::ModelLoadError { .. })
+        ));
+    }
+
+    fn tensor(name: &str, shape: Vec<OnnxDimension>) -> OnnxTensor {
+        OnnxTensor {
+            name: name.to_owned(),
+            element_type: "float",
+            shape: Some(shape),
+        }
+    }
+
+    fn graph(outputs: Vec<OnnxTensor>) -> OnnxGraph {
+        OnnxGraph {
+            ir_version: 8,
+            producer: "pytorch 2.1.0".to_owned(),
+            opsets: vec![OnnxOpset {
+                domain: String::new(),
+                version: 14,
+            }],
+            inputs: vec![tensor(
+                "input_ids",
+                vec![
+                    OnnxDimension::Symbolic("batch_size".to_owned()),
+                    OnnxDimension::Symbolic("sequence_length".to_owned()),
+                ],
+            )],
+            outputs,
+        }
+    }
+
+    #[test]
+    fn describe_detected_output() {
+        let description = describe_onnx(
+            &graph(vec![tensor(
+                "last_hidden_state",
+                vec![
+                    OnnxDimension::Symbolic("batch_size".to_owned()),
+                    OnnxDimension::Unknown,
+                    OnnxDimension::Fixed(384),
+                ],
+            )]),
+            None,
+        );
+
+        assert_eq!(description.opsets.get("ai.onnx"), Some(&14));
+        assert_eq!(description.output_key.as_deref(), Some("last_hidden_state"));
+        assert_eq!(description.output_key_source, Some("detected"));
+        assert_eq!(description.dimension, Some(384));
+        assert_eq!(description.output_error, None);
+    }
+
+    #[test]
+    fn describe_undecidable_output() {
+        let logits = |name| tensor(name, vec![OnnxDimension::Unknown, OnnxDimension::Unknown]);
+        let description = describe_onnx(
+            &graph(vec![logits("start_logits"), logits("end_logits")]),
+            None,
+        );
+
+        assert_eq!(description.output_key, None);
+        assert_eq!(description.output_key_source, None);
+        assert_eq!(description.dimension, None);
+        let error = description
+            .output_error
+            .expect("The output should not be chosen.");
+        assert!(
+            error.contains("start_logits") && error.contains("end_logits"),
+            "{error}"
+        );
+
+        // A configured key must be one of the outputs.
+        let description = describe_onnx(
+            &graph(vec![logits("start_logits"), logits("end_logits")]),
+            Some("sentence_embedding"),
+        );
+        assert_eq!(description.output_key, None);
+        assert!(description.output_error.is_some());
+    }
+
+    #[test]
+    fn verify_files_against_manifest() {
+        let contents = [
+            ("model.onnx", b"model".to_vec()),
+            ("tokenizer.json", b"tokenizer".to_vec()),
+        ];
+        let manifest = contents
+            .iter()
+            .map(|(name, bytes)| ((*name).to_owned(), integrity::sha256_hex(bytes)))
+            .collect::<HashMap<_, _>>();
+        let path = Path::new("model");
+
+        let files = describe_files(&contents, &manifest);
+        assert!(files.iter().all(|file| file.verified == Some(true)));
+        assert_eq!(
+            verified_fingerprint(path, &files).expect("The files should be verified."),
+            integrity::fingerprint(files.iter().map(|file| file.sha256.as_str()))
+        );
+
+        let mut mismatched = manifest.clone();
+        mismatched.insert("model.onnx".to_owned(), integrity::sha256_hex(b"other"));
+        let files = describe_files(&contents, &mismatched);
+        assert_eq!(files[0].verified, Some(false));
+        assert!(matches!(
+            verified_fingerprint(path, &files),
+            Err(EmbedderError::ModelIntegrityError { error, .. }) if error.starts_with("does not match")
+        ));
+
+        let mut unlisted = manifest;
+        unlisted.remove("tokenizer.json");
+        let files = describe_files(&contents, &unlisted);
+        assert_eq!(files[1].verified, None);
+        assert!(matches!(
+            verified_fingerprint(path, &files),
+            Err(EmbedderError::ModelIntegrityError { error, .. }) if error.starts_with("not listed")
+        ));
+    }
+
+    fn inspection() -> ModelInspection {
+        let file = |name: &str, verified| FileDescription {
+            name: name.to_owned(),
+            size: 4,
+            sha256: integrity::sha256_hex(name.as_bytes()),
+            verified,
+        };
+
+        ModelInspection {
+            path: "models/custom".to_owned(),
+            files: vec![
+                file("model.onnx", Some(true)),
+                file("tokenizer.json", Some(false)),
+                file("tokenizer_config.json", None),
+            ],
+            onnx: describe_onnx(
+                &graph(vec![tensor(
+                    "last_hidden_state",
+                    vec![
+                        OnnxDimension::Symbolic("batch_size".to_owned()),
+                        OnnxDimension::Unknown,
+                        OnnxDimension::Fixed(384),
+                    ],
+                )]),
+                None,
+            ),
+            tokenizer: describe_tokenizer(
+                &tokenizer_file(),
+                Some(br#"{"additional_special_tokens": ["[MISSING]"]}"#),
+                b"{}",
+            )
+            .expect("Failed to describe the tokenizer."),
+            sample: None,
+            sample_error: Some("model.onnx: does not match SHA256SUMS".to_owned()),
+        }
+    }
+
+    #[test]
+    fn write_inspection_text() {
+        let mut text = String::new();
+        write_text(&inspection(), &mut text).expect("Failed to write the inspection.");
+
+        for expected in [
+            "verified",
+            "MISMATCH",
+            "not in SHA256SUMS",
+            "ONNX: IR version 8, produced by pytorch 2.1.0, opsets ai.onnx 14",
+            "(batch_size, ?, 384)",
+            "Embeddings from 'last_hidden_state' (detected), dimension 384",
+            "Tokenizer: WordLevel, 5 tokens, model_max_length unset, truncation none",
+            "(added token)",
+            "not in the vocabulary",
+            "Sample: the model could not be loaded: model.onnx: does not match SHA256SUMS",
+        ] {
+            assert!(text.contains(expected), "{expected:?} not in:\n{text}");
+        }
+    }
+
+    #[test]
+    fn write_inspection_json() {
+        let json = serde_json::to_value(inspection()).expect("Failed to serialize the inspection.");
+
+        assert_eq!(json["onnx"]["outputKey"], "last_hidden_state");
+        assert_eq!(json["onnx"]["outputKeySource"], "detected");
+        assert_eq!(
+            json["onnx"]["outputs"][0]["shape"],
+            serde_json::json!(["batch_size", null, 384])
+        );
+        assert_eq!(json["files"][1]["verified"], false);
+        assert_eq!(json["tokenizer"]["modelType"], "WordLevel");
+        assert_eq!(json["sampleError"], "model.onnx: does not match SHA256SUMS");
+        assert!(json["sample"].is_null());
+        // The errors are skipped when there are none.
+        assert!(json["onnx"].get("outputError").is_none());
+    }
+}
@@ -6,12 +6,22 @@ use embedder_external::axum::{
 use embedder_external::clap::Parser;
 
 mod args;
-use args::CliArgs;
+use args::{CliArgs, Command};
+
+mod cache;
+use cache::TokenCache;
 
 mod common;
 
+mod encoding;
+
 mod endpoints;
 
+mod inspect;
+
+#[cfg(feature = "grpc")]
+mod grpc;
+
 #[cfg(feature = "status")]
 mod status;
 #[cfg(feature = "status")]
@@ -24,12 +34,34 @@ async fn main() -> Result<(), EmbedderAPIError> {
     // Parse our command line arguments
     let args = CliArgs::try_parse().map_err(EmbedderAPIError::ArgsError)?;
 
+    if let Some(Command::InspectModel(inspect_args)) = args.command() {
+        return inspect::run(inspect_args);
+    }
+
     let socket_addr = args.socket_addr();
 
+    // The runtime models need to be loaded before the routes are built for each model.
+    if let Some(model_dir) = args.model_dir() {
+        let model_dir = model_dir.to_owned();
+        let models_manifest = args.models_manifest().map(ToOwned::to_owned);
+
+        common::spawn_blocking(move || {
+            endpoints::load_runtime_models(&model_dir, models_manifest.as_deref())
+        })
+        .await?;
+    }
+
     // build our application with a single route
     let app = Router::new()
         .route("/", get(endpoints::root))
-        .route("/embed", post(endpoints::embed));
+        .route("/embed", post(endpoints::embed))
+        .route("/embed_images", post(endpoints::embed_images))
+        .route("/maxsim", post(endpoints::maxsim))
+        .route("/models", get(endpoints::models))
+        .route("/rerank", post(endpoints::rerank))
+        .route("/tokenize", post(endpoints::tokenize))
+        .route("/detokenize", post(endpoints::detokenize))
+        .nest("/tei", endpoints::tei::router());
 
     // run our app with hyper, listening globally on port 3000
     let listener = tokio::net::TcpListener::bind(socket_addr).await.unwrap();
@@ -37,12 +69,26 @@ async fn main() -> Result<(), EmbedderAPIError> {
     #[cfg(feature = "status")]
     Status::init();
 
+    TokenCache::init(args.token_cache_size());
+
+    if args.preload() {
+        common::spawn_blocking(endpoints::preload_models).await?;
+    }
+
+    #[cfg(feature = "grpc")]
+    let grpc_server = grpc::serve(args.grpc_socket_addr());
+    #[cfg(not(feature = "grpc"))]
+    let grpc_server = std::future::pending::<Result<(), EmbedderAPIError>>();
+
     tokio::select!(
         _ = tokio::signal::ctrl_c() => {
             Err(EmbedderAPIError::UserTerminated)
         },
         err = axum::serve(listener, app.clone()) => {
             err.map_err(EmbedderAPIError::IoError)
+        },
+        err = grpc_server => {
+            err
         }
     )
 }
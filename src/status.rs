@@ -7,12 +7,12 @@ use memory_stats::memory_stats;
 use tokio::time::Instant;
 
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, OnceLock};
+use std::sync::{Arc, Mutex, OnceLock};
 
 /// The global status of the server.
 static GLOBAL_STATUS: OnceLock<Arc<Status>> = OnceLock::new();
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MemoryUsage {
     physical_used: usize,
@@ -33,11 +33,66 @@ impl MemoryUsage {
     }
 }
 
+/// The memory used by the server before and after loading a model.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelLoad {
+    name: &'static str,
+    duration: f32,
+    before: Option<MemoryUsage>,
+    after: Option<MemoryUsage>,
+}
+
+impl ModelLoad {
+    /// Measure the time and memory taken by `load`.
+    pub fn measure<T, E>(
+        name: &'static str,
+        load: impl FnOnce() -> Result<T, E>,
+    ) -> Result<(T, Self), E> {
+        let before = MemoryUsage::new();
+        let start = Instant::now();
+        let loaded = load()?;
+
+        Ok((
+            loaded,
+            Self {
+                name,
+                duration: start.elapsed().as_secs_f32(),
+                before,
+                after: MemoryUsage::new(),
+            },
+        ))
+    }
+
+    /// The increase of the physical memory used, in bytes.
+    pub fn physical_increase(&self) -> Option<isize> {
+        Some(
+            self.after.as_ref()?.physical_used as isize
+                - self.before.as_ref()?.physical_used as isize,
+        )
+    }
+}
+
+impl std::fmt::Display for ModelLoad {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Loaded {} in {:.2}s", self.name, self.duration)?;
+        match self.physical_increase() {
+            Some(increase) => write!(
+                f,
+                ", physical memory {:+.1} MiB.",
+                increase as f64 / (1024.0 * 1024.0)
+            ),
+            None => write!(f, "."),
+        }
+    }
+}
+
 /// A singleton struct to hold the status of the server.
 #[derive(Debug)]
 pub struct Status {
     start_time: Instant,
     requests: AtomicUsize,
+    model_loads: Mutex<Vec<ModelLoad>>,
 }
 
 impl Serialize for Status {
@@ -45,10 +100,17 @@ impl Serialize for Status {
     where
         S: serde::Serializer,
     {
-        let mut state = serializer.serialize_struct("Status", 3)?;
+        let mut state = serializer.serialize_struct("Status", 4)?;
         state.serialize_field("uptime", &self.start_time.elapsed().as_secs_f32())?;
         state.serialize_field("requests", &self.requests.load(Ordering::Relaxed))?;
         state.serialize_field("memory", &MemoryUsage::new())?;
+        state.serialize_field(
+            "modelLoads",
+            &*self
+                .model_loads
+                .lock()
+                .map_err(|err| serde::ser::Error::custom(err.to_string()))?,
+        )?;
         state.end()
     }
 }
@@ -65,6 +127,7 @@ impl Status {
             Arc::new(Self {
                 start_time: Instant::now(),
                 requests: AtomicUsize::new(0),
+                model_loads: Mutex::new(Vec::new()),
             })
         }))
     }
@@ -74,4 +137,11 @@ impl Status {
     pub fn increment_requests(&self) {
         self.requests.fetch_add(1, Ordering::Relaxed);
     }
+
+    /// Record the memory taken by loading a model.
+    pub fn record_model_load(&self, load: ModelLoad) {
+        if let Ok(mut model_loads) = self.model_loads.lock() {
+            model_loads.push(load);
+        }
+    }
 }